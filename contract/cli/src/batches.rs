@@ -0,0 +1,221 @@
+use serde_json::{json, Map, Value};
+use std::fs;
+use std::path::{Path, PathBuf};
+use votesmart::MAX_EVENTS_DATA_LENGTH;
+
+/// Fields of a logged change besides the values: entity, id, editor and the
+/// timestamps a stored recommendation gets.
+const EVENT_ENTRY_OVERHEAD: usize = 256;
+
+/// Bytes of rows in one call. The contract parses the arguments before it writes
+/// the first row, how many rows fit the gas of the call is decided by the contract.
+pub const MAX_CALL_ROWS_LENGTH: usize = 64 * 1024;
+
+/// Bytes of rows in one proposal. The last approval applies all of them at once,
+/// so unlike an import the batch has to fit the gas of a single call.
+pub const MAX_PROPOSAL_ROWS_LENGTH: usize = 8 * 1024;
+
+/// Splits the rows into calls of at most `max_length` bytes of JSON. A row whose change
+/// can't be logged even alone, counted as replacing a stored row of the same size, is refused.
+pub fn split_batches(rows: Vec<Value>, max_length: usize) -> Result<Vec<Vec<Value>>, String> {
+    let mut batches = vec![];
+    let mut batch: Vec<Value> = vec![];
+    let mut length = 0;
+    for (index, row) in rows.into_iter().enumerate() {
+        let row_length = row.to_string().len() + 1;
+        let row_log_length = 2 * row_length + EVENT_ENTRY_OVERHEAD;
+        if row_log_length > MAX_EVENTS_DATA_LENGTH {
+            return Err(format!(
+                "row {}: too long to be logged, {} bytes",
+                index, row_log_length
+            ));
+        }
+        if !batch.is_empty() && length + row_length > max_length {
+            batches.push(std::mem::take(&mut batch));
+            length = 0;
+        }
+        length += row_length;
+        batch.push(row);
+    }
+    if !batch.is_empty() {
+        batches.push(batch);
+    }
+    Ok(batches)
+}
+
+/// Writes `<method>-001.json`, `<method>-002.json`, ... with the arguments of each call
+/// and returns the paths in the order the calls have to be sent. The files of a previous
+/// run for the same method are removed, so none of them is sent by mistake.
+/// `extra` holds the arguments besides the rows, e.g. `publish_at` or `resume_token`.
+pub fn write_batches(
+    out_dir: &Path,
+    method: &str,
+    argument: &str,
+    extra: &Map<String, Value>,
+    batches: Vec<Vec<Value>>,
+) -> Result<Vec<PathBuf>, String> {
+    let dir_error = |error: std::io::Error| format!("{}: {}", out_dir.display(), error);
+    fs::create_dir_all(out_dir).map_err(dir_error)?;
+    let prefix = format!("{}-", method);
+    for entry in fs::read_dir(out_dir).map_err(dir_error)? {
+        let path = entry.map_err(dir_error)?.path();
+        let name = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or("");
+        if name.starts_with(&prefix) && name.ends_with(".json") {
+            fs::remove_file(&path).map_err(|error| format!("{}: {}", path.display(), error))?;
+        }
+    }
+
+    let mut paths = vec![];
+    for (index, batch) in batches.into_iter().enumerate() {
+        let path = out_dir.join(format!("{}-{:03}.json", method, index + 1));
+        let mut args = extra.clone();
+        args.insert(argument.to_string(), json!(batch));
+        write_args(&path, args)?;
+        paths.push(path);
+    }
+    Ok(paths)
+}
+
+/// Writes the arguments of the call that continues the one in `path` at `resume_token`:
+/// the rows from the token position on and the token, next to the original file.
+/// Returns the path and the method, taken from the file name.
+pub fn write_resumed(path: &Path, resume_token: &str) -> Result<(PathBuf, String), String> {
+    let file_error = |error: &dyn std::fmt::Display| format!("{}: {}", path.display(), error);
+    let position: usize = resume_token
+        .split(':')
+        .next()
+        .and_then(|position| position.parse().ok())
+        .ok_or_else(|| format!("Invalid resume token: {}", resume_token))?;
+    let stem = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .ok_or_else(|| file_error(&"not a file written by the tool"))?;
+    let method = stem.split('-').next().unwrap_or("").to_string();
+    let text = fs::read_to_string(path).map_err(|error| file_error(&error))?;
+    let mut args: Map<String, Value> =
+        serde_json::from_str(&text).map_err(|error| file_error(&error))?;
+
+    // rows keep their position in the first file, so the token counts from its first row
+    let first = match args.get("resume_token") {
+        Some(Value::String(token)) => token
+            .split(':')
+            .next()
+            .and_then(|position| position.parse().ok())
+            .ok_or_else(|| file_error(&"invalid resume_token"))?,
+        Some(Value::Null) => 0,
+        _ => return Err(file_error(&"only import calls are resumed")),
+    };
+    let rows = args
+        .values_mut()
+        .find_map(|value| value.as_array_mut())
+        .ok_or_else(|| file_error(&"no rows"))?;
+    if position < first || position - first >= rows.len() {
+        return Err(format!(
+            "{}: the resume token points to row {}, the file has rows {} to {}",
+            path.display(),
+            position,
+            first,
+            first + rows.len() - 1
+        ));
+    }
+    rows.drain(..position - first);
+    args.insert(
+        "resume_token".to_string(),
+        Value::String(resume_token.to_string()),
+    );
+
+    let original = stem.split("-from-").next().unwrap_or(stem);
+    let resumed = path.with_file_name(format!("{}-from-{}.json", original, position));
+    write_args(&resumed, args)?;
+    Ok((resumed, method))
+}
+
+fn write_args(path: &Path, args: Map<String, Value>) -> Result<(), String> {
+    fs::write(path, Value::Object(args).to_string())
+        .map_err(|error| format!("{}: {}", path.display(), error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rows(count: u64) -> Vec<Value> {
+        (0..count).map(|id| json!([id, "Party"])).collect()
+    }
+
+    fn out_dir(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("votesmart-cli-{}-{}", name, std::process::id()))
+    }
+
+    fn read_rows(path: &Path) -> (Vec<Value>, Value) {
+        let args: Value = serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap();
+        (
+            args["parties"].as_array().unwrap().clone(),
+            args["resume_token"].clone(),
+        )
+    }
+
+    #[test]
+    fn batches_stay_within_the_limit() {
+        // each row is 12 bytes with the separator
+        let batches = split_batches(rows(5), 24).unwrap();
+        let sizes: Vec<usize> = batches.iter().map(Vec::len).collect();
+        assert_eq!(sizes, vec![2, 2, 1]);
+        // a row longer than the limit still gets a batch of its own
+        let batches = split_batches(rows(2), 5).unwrap();
+        assert_eq!(batches.len(), 2);
+    }
+
+    #[test]
+    fn row_too_long_to_log_is_refused() {
+        let row = json!([1, "x".repeat(MAX_EVENTS_DATA_LENGTH / 2)]);
+        let error = split_batches(vec![json!([0, "Party"]), row], MAX_CALL_ROWS_LENGTH)
+            .err()
+            .unwrap();
+        assert!(error.starts_with("row 1: too long to be logged"));
+    }
+
+    #[test]
+    fn resumed_rows_start_at_the_token() {
+        let dir = out_dir("resume");
+        let mut extra = Map::new();
+        extra.insert("resume_token".to_string(), Value::Null);
+        let paths =
+            write_batches(&dir, "import_parties", "parties", &extra, vec![rows(5)]).unwrap();
+
+        let (path, method) = write_resumed(&paths[0], "3:aa").unwrap();
+        assert_eq!(method, "import_parties");
+        assert_eq!(path, dir.join("import_parties-001-from-3.json"));
+        let (resumed, token) = read_rows(&path);
+        assert_eq!(resumed, rows(5)[3..].to_vec());
+        assert_eq!(token, json!("3:aa"));
+
+        // positions count from the first row of the original file
+        let (path, _) = write_resumed(&path, "4:bb").unwrap();
+        assert_eq!(path, dir.join("import_parties-001-from-4.json"));
+        assert_eq!(read_rows(&path).0, rows(5)[4..].to_vec());
+
+        let error = write_resumed(&path, "2:cc").err().unwrap();
+        assert!(error.ends_with("the resume token points to row 2, the file has rows 4 to 4"));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn proposals_are_not_resumed() {
+        let dir = out_dir("proposal");
+        let paths = write_batches(
+            &dir,
+            "add_detailed_recommendations",
+            "recommendations",
+            &Map::new(),
+            vec![rows(2)],
+        )
+        .unwrap();
+        let error = write_resumed(&paths[0], "1:aa").err().unwrap();
+        assert!(error.ends_with("only import calls are resumed"));
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,192 @@
+//! Checks CSV or JSON datasets against the contract types and writes the arguments
+//! of the `import_*` calls. Works offline, the written files are signed and sent with
+//! near-cli, a call the contract stopped early is continued with `resume`. Recommendations
+//! that need several approvals are written as `add_detailed_recommendations` proposals.
+
+mod batches;
+mod rows;
+
+use std::env;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use serde_json::{Map, Value};
+
+use crate::batches::{
+    split_batches, write_batches, write_resumed, MAX_CALL_ROWS_LENGTH, MAX_PROPOSAL_ROWS_LENGTH,
+};
+use crate::rows::{load, Kind};
+
+const USAGE: &str = "Usage: votesmart-cli <parties|regions|districts|candidates|recommendations> <FILE.csv|FILE.json>
+       votesmart-cli resume <BATCH.json> <RESUME_TOKEN>
+    [--out DIR]            directory for the argument files, `batches` by default
+    [--publish-at NS]      publication time of the imported recommendations
+    [--approvals-required N]  approvals of a recommendation batch, see `get_approval_policy`, 1 by default
+    [--contract ACCOUNT]   contract account for the printed commands, `votesmart.near` by default
+    [--account ACCOUNT]    signer account for the printed commands";
+
+/// Attached gas of the printed commands, the maximum of a transaction.
+const CALL_GAS: &str = "300000000000000";
+
+enum Command {
+    Prepare(Kind, PathBuf),
+    /// Continues the call of the file at the token it returned.
+    Resume(PathBuf, String),
+}
+
+struct Options {
+    command: Command,
+    out_dir: PathBuf,
+    publish_at: Option<String>,
+    approvals_required: u32,
+    contract: String,
+    account: String,
+}
+
+fn main() {
+    let options = match parse_options(env::args().skip(1).collect()) {
+        Ok(options) => options,
+        Err(error) => {
+            eprintln!("{}\n\n{}", error, USAGE);
+            process::exit(2);
+        }
+    };
+    if let Err(errors) = run(options) {
+        for error in errors {
+            eprintln!("error: {}", error);
+        }
+        process::exit(1);
+    }
+}
+
+fn run(options: Options) -> Result<(), Vec<String>> {
+    let (kind, input) = match options.command {
+        Command::Prepare(kind, ref input) => (kind, input),
+        Command::Resume(ref path, ref resume_token) => {
+            let (path, method) = write_resumed(path, resume_token).map_err(|error| vec![error])?;
+            print_call(&options, &method, &path);
+            return Ok(());
+        }
+    };
+    let mut dataset = load(kind, input)?;
+    for warning in dataset.warnings.iter() {
+        eprintln!("warning: {}", warning);
+    }
+
+    // import_recommendations is refused while batches need several approvals
+    let proposal = matches!(kind, Kind::Recommendations) && options.approvals_required > 1;
+    let mut extra = Map::new();
+    if let Kind::Recommendations = kind {
+        extra.insert(
+            "publish_at".to_string(),
+            options
+                .publish_at
+                .clone()
+                .map_or(Value::Null, Value::String),
+        );
+    }
+    let max_length = if proposal {
+        dataset.method = "add_detailed_recommendations";
+        MAX_PROPOSAL_ROWS_LENGTH
+    } else {
+        extra.insert("resume_token".to_string(), Value::Null);
+        MAX_CALL_ROWS_LENGTH
+    };
+    let total = dataset.rows.len();
+    let batches = split_batches(dataset.rows, max_length).map_err(|error| vec![error])?;
+    let paths = write_batches(
+        &options.out_dir,
+        dataset.method,
+        dataset.argument,
+        &extra,
+        batches,
+    )
+    .map_err(|error| vec![error])?;
+
+    if proposal {
+        eprintln!(
+            "{} rows in {} proposals of {}. Each call returns a proposal id, the batch \
+             is applied after {} more curators call approve_recommendations with it:",
+            total,
+            paths.len(),
+            dataset.method,
+            options.approvals_required - 1
+        );
+    } else {
+        eprintln!(
+            "{} rows in {} files of {}, send them in this order. If a call returns \
+             a resume_token, continue it with `votesmart-cli resume <FILE> <TOKEN>` first:",
+            total,
+            paths.len(),
+            dataset.method
+        );
+    }
+    for path in paths {
+        print_call(&options, dataset.method, &path);
+    }
+    Ok(())
+}
+
+fn print_call(options: &Options, method: &str, path: &Path) {
+    println!(
+        "near call {} {} \"$(cat {})\" --accountId {} --gas {}",
+        options.contract,
+        method,
+        path.display(),
+        options.account,
+        CALL_GAS
+    );
+}
+
+fn parse_options(args: Vec<String>) -> Result<Options, String> {
+    let mut args = args.into_iter();
+    let kind = args.next().ok_or("Missing the kind of rows")?;
+    let command = if kind == "resume" {
+        let path = PathBuf::from(args.next().ok_or("Missing the batch file")?);
+        Command::Resume(path, args.next().ok_or("Missing the resume token")?)
+    } else {
+        let kind = Kind::parse(&kind).ok_or_else(|| format!("Unknown kind of rows: {}", kind))?;
+        Command::Prepare(
+            kind,
+            PathBuf::from(args.next().ok_or("Missing the input file")?),
+        )
+    };
+    let mut options = Options {
+        command,
+        out_dir: PathBuf::from("batches"),
+        publish_at: None,
+        approvals_required: 1,
+        contract: "votesmart.near".to_string(),
+        account: "YOUR-ACCOUNT.near".to_string(),
+    };
+    while let Some(flag) = args.next() {
+        let value = args
+            .next()
+            .ok_or_else(|| format!("Missing the value of {}", flag))?;
+        match flag.as_str() {
+            "--out" => options.out_dir = PathBuf::from(value),
+            "--publish-at" => match value.parse::<u64>() {
+                Ok(_) => options.publish_at = Some(value),
+                _ => return Err(format!("Invalid publication time: {}", value)),
+            },
+            "--approvals-required" => match value.parse::<u32>() {
+                Ok(approvals) if approvals > 0 => options.approvals_required = approvals,
+                _ => return Err(format!("Invalid number of approvals: {}", value)),
+            },
+            "--contract" => options.contract = value,
+            "--account" => options.account = value,
+            _ => return Err(format!("Unknown option: {}", flag)),
+        }
+    }
+    match options.command {
+        Command::Prepare(Kind::Recommendations, _) => {}
+        _ if options.publish_at.is_some() => {
+            return Err("--publish-at applies to recommendations only".to_string())
+        }
+        _ if options.approvals_required > 1 => {
+            return Err("--approvals-required applies to recommendations only".to_string())
+        }
+        _ => {}
+    }
+    Ok(options)
+}
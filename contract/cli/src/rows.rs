@@ -0,0 +1,484 @@
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet};
+use std::fmt::Display;
+use std::fs::File;
+use std::hash::Hash;
+use std::io::BufReader;
+use std::path::Path;
+use votesmart::{Candidate, CandidateStatus, District, RecommendationInput, Region, UnitType};
+
+#[derive(Clone, Copy)]
+pub enum Kind {
+    Parties,
+    Regions,
+    Districts,
+    Candidates,
+    Recommendations,
+}
+
+impl Kind {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "parties" => Some(Kind::Parties),
+            "regions" => Some(Kind::Regions),
+            "districts" => Some(Kind::Districts),
+            "candidates" => Some(Kind::Candidates),
+            "recommendations" => Some(Kind::Recommendations),
+            _ => None,
+        }
+    }
+}
+
+/// Rows checked and converted to the arguments of the contract method.
+pub struct Dataset {
+    pub method: &'static str,
+    /// Name of the rows argument of `method`.
+    pub argument: &'static str,
+    pub rows: Vec<Value>,
+    /// Problems the contract may or may not accept, depending on the stored data.
+    pub warnings: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct TitleRow {
+    id: u64,
+    title: String,
+}
+
+#[derive(Deserialize)]
+struct DistrictRow {
+    id: u64,
+    region_id: u64,
+    title: String,
+    #[serde(default)]
+    unit_type: Option<UnitType>,
+    #[serde(default)]
+    parent_id: Option<u64>,
+    #[serde(default)]
+    address: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct CandidateRow {
+    id: u64,
+    title: String,
+    party_id: u64,
+    #[serde(default)]
+    status: Option<CandidateStatus>,
+}
+
+#[derive(Deserialize)]
+struct RecommendationRow {
+    campaign_id: u64,
+    district_id: u64,
+    candidate_ids: CandidateIds,
+    #[serde(default)]
+    seats: Option<u32>,
+    #[serde(default)]
+    rationale: Option<String>,
+}
+
+/// Ranked candidates, a JSON array or a CSV cell like `101;102`.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum CandidateIds {
+    List(Vec<u64>),
+    One(u64),
+    Text(String),
+}
+
+impl CandidateIds {
+    fn parse(self) -> Result<Vec<u64>, String> {
+        match self {
+            CandidateIds::List(ids) => Ok(ids),
+            CandidateIds::One(id) => Ok(vec![id]),
+            CandidateIds::Text(text) => text
+                .split(';')
+                .map(str::trim)
+                .filter(|id| !id.is_empty())
+                .map(|id| {
+                    id.parse()
+                        .map_err(|_| format!("invalid candidate id \"{}\"", id))
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Reads the file and checks the rows the way the contract does, as far as it can be
+/// done without the stored data. Returns all the invalid rows at once.
+pub fn load(kind: Kind, path: &Path) -> Result<Dataset, Vec<String>> {
+    match kind {
+        Kind::Parties => load_parties(path),
+        Kind::Regions => load_regions(path),
+        Kind::Districts => load_districts(path),
+        Kind::Candidates => load_candidates(path),
+        Kind::Recommendations => load_recommendations(path),
+    }
+}
+
+fn load_parties(path: &Path) -> Result<Dataset, Vec<String>> {
+    let rows: Vec<TitleRow> = read_rows(path)?;
+    let mut errors = vec![];
+    check_unique(rows.iter().map(|row| row.id), "party", &mut errors);
+    check_titles(rows.iter().map(|row| &row.title), &mut errors);
+    finish(errors)?;
+    Ok(Dataset {
+        method: "import_parties",
+        argument: "parties",
+        rows: rows
+            .into_iter()
+            .map(|row| json!([row.id, row.title]))
+            .collect(),
+        warnings: vec![],
+    })
+}
+
+fn load_regions(path: &Path) -> Result<Dataset, Vec<String>> {
+    let rows: Vec<TitleRow> = read_rows(path)?;
+    let mut errors = vec![];
+    check_unique(rows.iter().map(|row| row.id), "region", &mut errors);
+    check_titles(rows.iter().map(|row| &row.title), &mut errors);
+    finish(errors)?;
+    Ok(Dataset {
+        method: "import_regions",
+        argument: "regions",
+        rows: rows
+            .into_iter()
+            .map(|row| row_value(row.id, Region { title: row.title }))
+            .collect(),
+        warnings: vec![],
+    })
+}
+
+fn load_districts(path: &Path) -> Result<Dataset, Vec<String>> {
+    let rows: Vec<DistrictRow> = read_rows(path)?;
+    let mut errors = vec![];
+    let mut warnings = vec![];
+    check_unique(rows.iter().map(|row| row.id), "district", &mut errors);
+    check_titles(rows.iter().map(|row| &row.title), &mut errors);
+
+    let region_of: HashMap<u64, u64> = rows.iter().map(|row| (row.id, row.region_id)).collect();
+    for (index, row) in rows.iter().enumerate() {
+        if row.unit_type == Some(UnitType::Region) {
+            errors.push(format!(
+                "row {} (district {}): regions are added with add_regions",
+                index, row.id
+            ));
+        }
+        if let Some(parent_id) = row.parent_id {
+            match region_of.get(&parent_id) {
+                None => warnings.push(format!(
+                    "row {} (district {}): parent_id {} is not in the file, it has to be added before",
+                    index, row.id, parent_id
+                )),
+                Some(region_id) if *region_id != row.region_id => errors.push(format!(
+                    "row {} (district {}): parent_id {} is in region {}",
+                    index, row.id, parent_id, region_id
+                )),
+                _ => {}
+            }
+        }
+    }
+
+    let districts = rows
+        .into_iter()
+        .map(|row| {
+            (
+                row.id,
+                District {
+                    region_id: row.region_id,
+                    title: row.title,
+                    unit_type: row.unit_type.unwrap_or_default(),
+                    parent_id: row.parent_id,
+                    address: row.address,
+                },
+            )
+        })
+        .collect();
+    let districts = parents_first(districts, &mut errors);
+    finish(errors)?;
+    Ok(Dataset {
+        method: "import_districts",
+        argument: "districts",
+        rows: districts
+            .into_iter()
+            .map(|(id, district)| row_value(id, district))
+            .collect(),
+        warnings,
+    })
+}
+
+fn load_candidates(path: &Path) -> Result<Dataset, Vec<String>> {
+    let rows: Vec<CandidateRow> = read_rows(path)?;
+    let mut errors = vec![];
+    check_unique(rows.iter().map(|row| row.id), "candidate", &mut errors);
+    check_titles(rows.iter().map(|row| &row.title), &mut errors);
+    finish(errors)?;
+    Ok(Dataset {
+        method: "import_candidates",
+        argument: "candidates",
+        rows: rows
+            .into_iter()
+            .map(|row| {
+                row_value(
+                    row.id,
+                    Candidate {
+                        title: row.title,
+                        party_id: row.party_id,
+                        status: row.status.unwrap_or_default(),
+                    },
+                )
+            })
+            .collect(),
+        warnings: vec![],
+    })
+}
+
+fn load_recommendations(path: &Path) -> Result<Dataset, Vec<String>> {
+    let rows: Vec<RecommendationRow> = read_rows(path)?;
+    let mut errors = vec![];
+    check_unique(
+        rows.iter()
+            .map(|row| format!("{}/{}", row.campaign_id, row.district_id)),
+        "campaign_id/district_id",
+        &mut errors,
+    );
+
+    let mut recommendations = vec![];
+    for (index, row) in rows.into_iter().enumerate() {
+        let candidate_ids = match row.candidate_ids.parse() {
+            Ok(candidate_ids) => candidate_ids,
+            Err(error) => {
+                errors.push(format!("row {}: {}", index, error));
+                continue;
+            }
+        };
+        let input = RecommendationInput {
+            campaign_id: row.campaign_id,
+            district_id: row.district_id,
+            candidate_ids,
+            seats: row.seats.unwrap_or(1),
+            rationale: row.rationale,
+        };
+        if input.seats == 0 || input.candidate_ids.len() < input.seats as usize {
+            errors.push(format!(
+                "row {}: {} seats need as many candidates",
+                index, input.seats
+            ));
+        }
+        let unique: HashSet<&u64> = input.candidate_ids.iter().collect();
+        if unique.len() != input.candidate_ids.len() {
+            errors.push(format!("row {}: candidate listed more than once", index));
+        }
+        recommendations.push(input);
+    }
+    finish(errors)?;
+    Ok(Dataset {
+        method: "import_recommendations",
+        argument: "recommendations",
+        rows: recommendations.iter().map(|input| json!(input)).collect(),
+        warnings: vec![],
+    })
+}
+
+/// CSV files need a header row with the field names, JSON files an array of objects.
+/// Empty CSV cells are treated as missing optional fields.
+fn read_rows<T: DeserializeOwned>(path: &Path) -> Result<Vec<T>, Vec<String>> {
+    let file_error = |error: &dyn Display| vec![format!("{}: {}", path.display(), error)];
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some("csv") => {
+            let mut reader = csv::ReaderBuilder::new()
+                .trim(csv::Trim::All)
+                .from_path(path)
+                .map_err(|error| file_error(&error))?;
+            let mut rows = vec![];
+            let mut errors = vec![];
+            for (index, row) in reader.deserialize().enumerate() {
+                match row {
+                    Ok(row) => rows.push(row),
+                    Err(error) => errors.push(format!("row {}: {}", index, error)),
+                }
+            }
+            finish(errors)?;
+            Ok(rows)
+        }
+        Some("json") => {
+            let file = File::open(path).map_err(|error| file_error(&error))?;
+            serde_json::from_reader(BufReader::new(file)).map_err(|error| file_error(&error))
+        }
+        _ => Err(file_error(&"expected a .csv or .json file")),
+    }
+}
+
+/// Orders the districts so that each unit comes after its parent, in the file order otherwise.
+/// Batches are sent one after another, so a parent is always stored before its units.
+fn parents_first(
+    districts: Vec<(u64, District)>,
+    errors: &mut Vec<String>,
+) -> Vec<(u64, District)> {
+    let positions: HashMap<u64, usize> = districts
+        .iter()
+        .enumerate()
+        .map(|(index, (id, _))| (*id, index))
+        .collect();
+    let mut placed = vec![false; districts.len()];
+    let mut order = Vec::with_capacity(districts.len());
+    for start in 0..districts.len() {
+        let mut chain = vec![];
+        let mut current = Some(start);
+        while let Some(index) = current {
+            if placed[index] {
+                break;
+            }
+            if chain.contains(&index) {
+                errors.push(format!(
+                    "row {} (district {}): parent_id loops back to the district",
+                    index, districts[index].0
+                ));
+                break;
+            }
+            chain.push(index);
+            current = districts[index]
+                .1
+                .parent_id
+                .and_then(|parent_id| positions.get(&parent_id).copied());
+        }
+        for index in chain.into_iter().rev() {
+            placed[index] = true;
+            order.push(index);
+        }
+    }
+
+    let mut districts: Vec<Option<(u64, District)>> = districts.into_iter().map(Some).collect();
+    order
+        .into_iter()
+        .filter_map(|index| districts[index].take())
+        .collect()
+}
+
+fn check_unique<K: Eq + Hash + Display>(
+    keys: impl Iterator<Item = K>,
+    name: &str,
+    errors: &mut Vec<String>,
+) {
+    let mut seen = HashSet::new();
+    for (index, key) in keys.enumerate() {
+        if seen.contains(&key) {
+            errors.push(format!(
+                "row {}: {} {} is listed more than once",
+                index, name, key
+            ));
+        } else {
+            seen.insert(key);
+        }
+    }
+}
+
+fn check_titles<'a>(titles: impl Iterator<Item = &'a String>, errors: &mut Vec<String>) {
+    for (index, title) in titles.enumerate() {
+        if title.trim().is_empty() {
+            errors.push(format!("row {}: empty title", index));
+        }
+    }
+}
+
+fn finish(errors: Vec<String>) -> Result<(), Vec<String>> {
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+/// `[id, value]` pair of the `import_*` arguments.
+fn row_value<V: serde::Serialize>(id: u64, value: V) -> Value {
+    json!([id, value])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn district(id: u64, parent_id: Option<u64>) -> (u64, District) {
+        (
+            id,
+            District {
+                region_id: 16,
+                title: format!("District {}", id),
+                unit_type: UnitType::default(),
+                parent_id,
+                address: None,
+            },
+        )
+    }
+
+    fn write_file(name: &str, text: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("votesmart-cli-rows-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(name);
+        fs::write(&path, text).unwrap();
+        path
+    }
+
+    #[test]
+    fn parents_first_moves_units_after_their_parents() {
+        let mut errors = vec![];
+        let districts = vec![
+            district(3, Some(2)),
+            district(2, Some(1)),
+            district(1, None),
+            district(4, Some(7)),
+        ];
+        let ids: Vec<u64> = parents_first(districts, &mut errors)
+            .into_iter()
+            .map(|(id, _)| id)
+            .collect();
+        assert_eq!(ids, vec![1, 2, 3, 4]);
+        assert!(errors.is_empty());
+    }
+
+    #[test]
+    fn parents_first_reports_cycles() {
+        let mut errors = vec![];
+        let districts = vec![
+            district(1, Some(2)),
+            district(2, Some(1)),
+            district(3, None),
+        ];
+        let ordered = parents_first(districts, &mut errors);
+        assert_eq!(ordered.len(), 3);
+        assert_eq!(
+            errors,
+            vec!["row 0 (district 1): parent_id loops back to the district".to_string()]
+        );
+    }
+
+    #[test]
+    fn candidate_ids_are_split_in_csv() {
+        let path = write_file(
+            "recommendations.csv",
+            "campaign_id,district_id,candidate_ids,seats\n1,5,101; 102,\n1,6,103,\n",
+        );
+        let dataset = load(Kind::Recommendations, &path).unwrap();
+        assert_eq!(dataset.rows[0]["candidate_ids"], json!([101, 102]));
+        assert_eq!(dataset.rows[0]["seats"], json!(1));
+        assert_eq!(dataset.rows[1]["candidate_ids"], json!([103]));
+    }
+
+    #[test]
+    fn invalid_candidate_ids_are_reported() {
+        let path = write_file(
+            "invalid-recommendations.csv",
+            "campaign_id,district_id,candidate_ids\n1,5,101;x\n",
+        );
+        let errors = load(Kind::Recommendations, &path).err().unwrap();
+        assert_eq!(
+            errors,
+            vec!["row 0: invalid candidate id \"x\"".to_string()]
+        );
+    }
+}
@@ -0,0 +1,331 @@
+use crate::*;
+
+/// Default delay before a proposed admin can take over, one day in nanoseconds.
+pub(crate) const DEFAULT_ADMIN_TRANSFER_DELAY: u64 = 24 * 60 * 60 * 1_000_000_000;
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    /// Manages roles and passes every other role check.
+    Owner,
+    /// Adds campaigns, parties, regions, districts and candidates.
+    DataEditor,
+    /// Adds recommendations.
+    RecommendationCurator,
+    /// Reviews the data, has no write access.
+    Auditor,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PendingAdmin {
+    pub account_id: AccountId,
+    pub proposed_at: U64,
+    /// `accept_admin` is allowed from this block timestamp on.
+    pub available_at: U64,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PendingAdminTransferDelay {
+    pub delay: U64,
+    pub proposed_at: U64,
+    /// `apply_admin_transfer_delay` is allowed from this block timestamp on.
+    pub available_at: U64,
+}
+
+#[near_bindgen]
+impl VoteSmart {
+    /// First step of the admin transfer. Replaces a previous proposal.
+    pub fn propose_admin(&mut self, admin_id: ValidAccountId) {
+        self.assert_access();
+        let now = env::block_timestamp();
+        let pending = PendingAdmin {
+            account_id: admin_id.into(),
+            proposed_at: now.into(),
+            available_at: (now + self.admin_transfer_delay).into(),
+        };
+        let old = self.pending_admin.replace(pending.clone());
+        emit_change(EntityChange::new(
+            "pending_admin",
+            (),
+            old.as_ref(),
+            Some(&pending),
+        ));
+    }
+
+    /// Second step of the admin transfer, called by the proposed account.
+    pub fn accept_admin(&mut self) {
+        let pending = self.pending_admin.take().expect("No pending admin");
+        assert_eq!(
+            env::predecessor_account_id(),
+            pending.account_id,
+            "No access"
+        );
+        assert!(
+            env::block_timestamp() >= pending.available_at.0,
+            "Admin transfer is still time-locked"
+        );
+        let old = std::mem::replace(&mut self.master_account_id, pending.account_id.clone());
+        emit_changes(vec![
+            EntityChange::new("pending_admin", (), Some(&pending), None),
+            EntityChange::new("admin", (), Some(&old), Some(&pending.account_id)),
+        ]);
+    }
+
+    pub fn cancel_admin_transfer(&mut self) {
+        self.assert_access();
+        let pending = self.pending_admin.take().expect("No pending admin");
+        emit_change(EntityChange::new("pending_admin", (), Some(&pending), None));
+    }
+
+    pub fn get_pending_admin(&self) -> Option<PendingAdmin> {
+        self.pending_admin.clone()
+    }
+
+    /// Proposes a delay in nanoseconds, it is applied by `apply_admin_transfer_delay` once
+    /// the current delay has passed. Replaces a previous proposal, the current delay
+    /// cancels it.
+    pub fn set_admin_transfer_delay(&mut self, delay: U64) {
+        self.assert_access();
+        let now = env::block_timestamp();
+        let pending = if delay.0 == self.admin_transfer_delay {
+            None
+        } else {
+            Some(PendingAdminTransferDelay {
+                delay,
+                proposed_at: now.into(),
+                available_at: (now + self.admin_transfer_delay).into(),
+            })
+        };
+        let old = std::mem::replace(&mut self.pending_admin_transfer_delay, pending.clone());
+        emit_change(EntityChange::new(
+            "pending_admin_transfer_delay",
+            (),
+            old.as_ref(),
+            pending.as_ref(),
+        ));
+    }
+
+    /// Applies the proposed delay, to admin proposals made after it.
+    pub fn apply_admin_transfer_delay(&mut self) {
+        self.assert_access();
+        let pending = self
+            .pending_admin_transfer_delay
+            .take()
+            .expect("No pending admin transfer delay");
+        assert!(
+            env::block_timestamp() >= pending.available_at.0,
+            "Admin transfer delay is still time-locked"
+        );
+        let old = std::mem::replace(&mut self.admin_transfer_delay, pending.delay.into());
+        emit_changes(vec![
+            EntityChange::new("pending_admin_transfer_delay", (), Some(&pending), None),
+            EntityChange::new(
+                "admin_transfer_delay",
+                (),
+                Some(&U64(old)),
+                Some(&pending.delay),
+            ),
+        ]);
+    }
+
+    pub fn get_pending_admin_transfer_delay(&self) -> Option<PendingAdminTransferDelay> {
+        self.pending_admin_transfer_delay.clone()
+    }
+
+    pub fn get_admin_transfer_delay(&self) -> U64 {
+        self.admin_transfer_delay.into()
+    }
+
+    pub fn grant_role(&mut self, account_id: ValidAccountId, role: Role) {
+        self.assert_role(Role::Owner);
+        let account_id: AccountId = account_id.into();
+        let mut roles = self.roles.get(&account_id).unwrap_or_default();
+        if !roles.contains(&role) {
+            roles.push(role);
+            let old = self.roles.insert(&account_id, &roles);
+            self.internal_charge_storage(StorageCollection::Roles);
+            emit_change(EntityChange::new(
+                "roles",
+                &account_id,
+                old.as_ref(),
+                Some(&roles),
+            ));
+        }
+    }
+
+    pub fn revoke_role(&mut self, account_id: ValidAccountId, role: Role) {
+        self.assert_role(Role::Owner);
+        let account_id: AccountId = account_id.into();
+        if let Some(old) = self.roles.get(&account_id) {
+            let roles: Vec<Role> = old.iter().copied().filter(|r| *r != role).collect();
+            if roles.len() == old.len() {
+                return;
+            }
+            let new = if roles.is_empty() {
+                self.roles.remove(&account_id);
+                None
+            } else {
+                self.roles.insert(&account_id, &roles);
+                Some(roles)
+            };
+            self.internal_charge_storage(StorageCollection::Roles);
+            emit_change(EntityChange::new(
+                "roles",
+                &account_id,
+                Some(&old),
+                new.as_ref(),
+            ));
+        }
+    }
+
+    /// Roles of the account. The master account is always an owner.
+    pub fn get_roles(&self, account_id: ValidAccountId) -> Vec<Role> {
+        let account_id: AccountId = account_id.into();
+        let mut roles = self.roles.get(&account_id).unwrap_or_default();
+        if account_id == self.master_account_id && !roles.contains(&Role::Owner) {
+            roles.insert(0, Role::Owner);
+        }
+        roles
+    }
+
+    pub fn get_accounts_with_roles(
+        &self,
+        cursor: Option<u64>,
+        limit: Option<u64>,
+    ) -> Page<(AccountId, Vec<Role>)> {
+        unordered_map_page(&self.roles, cursor, limit)
+    }
+
+    pub(crate) fn has_role(&self, account_id: &AccountId, role: Role) -> bool {
+        if *account_id == self.master_account_id {
+            return true;
+        }
+        self.roles
+            .get(account_id)
+            .map(|roles| roles.contains(&role) || roles.contains(&Role::Owner))
+            .unwrap_or(false)
+    }
+
+    pub(crate) fn assert_role(&self, role: Role) {
+        self.assert_not_migrating();
+        assert!(
+            self.has_role(&env::predecessor_account_id(), role),
+            "No access"
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::*;
+    use near_sdk::test_utils::accounts;
+
+    const DAY: u64 = DEFAULT_ADMIN_TRANSFER_DELAY;
+
+    #[test]
+    #[should_panic(expected = "No access")]
+    fn account_without_role_cannot_edit() {
+        let mut state = setup(0);
+        state.grant_role(accounts(1), Role::Auditor);
+        set_context(1, 0);
+        state.add_parties(vec![(2, "Party".to_string())]);
+    }
+
+    #[test]
+    fn roles_pass_their_checks() {
+        let mut state = setup(0);
+        state.grant_role(accounts(1), Role::DataEditor);
+        state.grant_role(accounts(2), Role::Owner);
+        set_context(1, 0);
+        state.add_parties(vec![(2, "Party".to_string())]);
+        assert!(!state.has_role(&accounts(1).into(), Role::RecommendationCurator));
+        // owners pass every role check
+        assert!(state.has_role(&accounts(2).into(), Role::RecommendationCurator));
+        set_context(2, 0);
+        state.revoke_role(accounts(1), Role::DataEditor);
+        assert!(state.get_roles(accounts(1)).is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "No access")]
+    fn editor_cannot_grant_roles() {
+        let mut state = setup(0);
+        state.grant_role(accounts(1), Role::DataEditor);
+        set_context(1, 0);
+        state.grant_role(accounts(1), Role::Owner);
+    }
+
+    #[test]
+    #[should_panic(expected = "Admin transfer is still time-locked")]
+    fn admin_transfer_waits_for_delay() {
+        let mut state = setup(0);
+        state.propose_admin(accounts(1));
+        set_context(1, DAY - 1);
+        state.accept_admin();
+    }
+
+    #[test]
+    #[should_panic(expected = "No access")]
+    fn admin_transfer_is_accepted_by_proposed_account() {
+        let mut state = setup(0);
+        state.propose_admin(accounts(1));
+        set_context(2, DAY);
+        state.accept_admin();
+    }
+
+    #[test]
+    fn admin_transfer_is_accepted_after_delay() {
+        let mut state = setup(0);
+        state.propose_admin(accounts(1));
+        set_context(1, DAY);
+        state.accept_admin();
+        assert_eq!(state.master_account_id, AccountId::from(accounts(1)));
+        assert!(state.get_pending_admin().is_none());
+        assert!(!state.has_role(&accounts(0).into(), Role::Owner));
+    }
+
+    #[test]
+    #[should_panic(expected = "No pending admin")]
+    fn cancelled_admin_transfer_is_not_accepted() {
+        let mut state = setup(0);
+        state.propose_admin(accounts(1));
+        state.cancel_admin_transfer();
+        set_context(1, DAY);
+        state.accept_admin();
+    }
+
+    #[test]
+    #[should_panic(expected = "Admin transfer delay is still time-locked")]
+    fn delay_change_waits_for_current_delay() {
+        let mut state = setup(0);
+        state.set_admin_transfer_delay(0.into());
+        set_context(0, DAY - 1);
+        state.apply_admin_transfer_delay();
+    }
+
+    #[test]
+    fn applied_delay_is_used_by_next_proposal() {
+        let mut state = setup(0);
+        state.set_admin_transfer_delay(0.into());
+        set_context(0, DAY);
+        state.apply_admin_transfer_delay();
+        assert_eq!(state.get_admin_transfer_delay().0, 0);
+        state.propose_admin(accounts(1));
+        set_context(1, DAY);
+        state.accept_admin();
+        assert!(state.has_role(&accounts(1).into(), Role::Owner));
+    }
+
+    #[test]
+    fn current_delay_cancels_pending_change() {
+        let mut state = setup(0);
+        state.set_admin_transfer_delay(0.into());
+        state.set_admin_transfer_delay(DAY.into());
+        assert!(state.get_pending_admin_transfer_delay().is_none());
+        assert_eq!(state.get_admin_transfer_delay().0, DAY);
+    }
+}
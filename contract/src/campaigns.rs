@@ -0,0 +1,286 @@
+use crate::*;
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Campaign {
+    pub title: String,
+    /// Nanosecond timestamp of the election day.
+    pub election_date: U64,
+    pub level: CampaignLevel,
+    pub status: CampaignStatus,
+    /// Recommendations are hidden from view methods before this timestamp.
+    pub publish_at: Option<U64>,
+    /// Set by `freeze_campaign`, recommendations can no longer change after it.
+    pub frozen_at: Option<U64>,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(rename_all = "snake_case")]
+pub enum CampaignLevel {
+    Federal,
+    Regional,
+    Municipal,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(rename_all = "snake_case")]
+pub enum CampaignStatus {
+    /// Being prepared, recommendations are not shown.
+    Draft,
+    Published,
+    /// The election is over, recommendations can no longer change.
+    Closed,
+    Archived,
+}
+
+impl CampaignStatus {
+    pub fn can_become(self, status: CampaignStatus) -> bool {
+        matches!(
+            (self, status),
+            (CampaignStatus::Draft, CampaignStatus::Published)
+                | (CampaignStatus::Draft, CampaignStatus::Archived)
+                | (CampaignStatus::Published, CampaignStatus::Closed)
+                | (CampaignStatus::Closed, CampaignStatus::Archived)
+        )
+    }
+
+    /// Whether recommendations of the campaign can still change.
+    pub fn is_open(self) -> bool {
+        matches!(self, CampaignStatus::Draft | CampaignStatus::Published)
+    }
+}
+
+#[near_bindgen]
+impl VoteSmart {
+    pub fn get_campaign(&self, id: u64, lang: Option<String>) -> Option<Campaign> {
+        let campaign = self.campaigns.get(&id)?;
+        Some(self.internal_localize(EntityKind::Campaign, id, campaign, &lang))
+    }
+
+    /// Allowed transitions: draft -> published -> closed -> archived, and draft -> archived.
+    pub fn set_campaign_status(&mut self, id: u64, status: CampaignStatus) {
+        self.assert_role(Role::Owner);
+        let mut campaign = self.campaigns.get(&id).expect("Campaign not found");
+        assert!(
+            campaign.status.can_become(status),
+            "Invalid campaign status transition"
+        );
+        campaign.status = status;
+        let old = self.campaigns.insert(&id, &campaign);
+        self.internal_charge_storage(StorageCollection::Campaigns);
+        emit_change(EntityChange::new(
+            "campaign",
+            id,
+            old.as_ref(),
+            Some(&campaign),
+        ));
+    }
+
+    /// Locks the recommendations of the campaign for good, along with the
+    /// candidates and districts they refer to. The time is kept in `frozen_at`.
+    pub fn freeze_campaign(&mut self, id: u64) {
+        self.assert_role(Role::Owner);
+        let mut campaign = self.campaigns.get(&id).expect("Campaign not found");
+        assert!(campaign.frozen_at.is_none(), "Campaign is already frozen");
+        campaign.frozen_at = Some(env::block_timestamp().into());
+        let old = self.campaigns.insert(&id, &campaign);
+        self.internal_charge_storage(StorageCollection::Campaigns);
+        emit_change(EntityChange::new(
+            "campaign",
+            id,
+            old.as_ref(),
+            Some(&campaign),
+        ));
+    }
+}
+
+impl VoteSmart {
+    pub(crate) fn internal_is_frozen(&self, campaign_id: u64) -> bool {
+        self.campaigns
+            .get(&campaign_id)
+            .map(|campaign| campaign.frozen_at.is_some())
+            .unwrap_or(false)
+    }
+
+    /// Panics if any of the recommendations belongs to a frozen campaign.
+    pub(crate) fn assert_not_frozen(
+        &self,
+        entity: &str,
+        id: u64,
+        recommendations: &[RecommendationIndex],
+    ) {
+        if let Some(index) = recommendations
+            .iter()
+            .find(|index| self.internal_is_frozen(index.campaign_id))
+        {
+            env::panic(
+                format!(
+                    "{} {} is referenced by frozen campaign {}",
+                    entity, id, index.campaign_id
+                )
+                .as_bytes(),
+            );
+        }
+    }
+
+    /// Panics if replacing a stored district changes a result of a frozen campaign: one of
+    /// its own recommendations, or one it or its units inherit from the old or new parents.
+    pub(crate) fn assert_district_not_frozen(&self, id: u64, district: &District) {
+        let old = match self.districts.get(&id) {
+            Some(old) => old,
+            None => return,
+        };
+        self.assert_not_frozen("District", id, &self.internal_district_recommendations(id));
+        let parent_ids = if old.parent_id == district.parent_id {
+            vec![old.parent_id]
+        } else {
+            vec![old.parent_id, district.parent_id]
+        };
+        for parent_id in parent_ids.iter() {
+            // a new parent may be added later in the same batch
+            let mut parent_id = *parent_id;
+            while let Some(ancestor_id) = parent_id {
+                self.assert_not_frozen(
+                    "Parent unit",
+                    ancestor_id,
+                    &self.internal_district_recommendations(ancestor_id),
+                );
+                parent_id = self
+                    .districts
+                    .get(&ancestor_id)
+                    .and_then(|ancestor| ancestor.parent_id);
+            }
+        }
+    }
+
+    /// Panics if the title of the entity is shown in a result of a frozen campaign.
+    pub(crate) fn assert_entity_not_frozen(&self, entity: EntityKind, id: u64) {
+        match entity {
+            EntityKind::Campaign => assert!(!self.internal_is_frozen(id), "Campaign is frozen"),
+            EntityKind::Party => {
+                for candidate_id in self.internal_party_candidates(id) {
+                    self.assert_not_frozen(
+                        "Party",
+                        id,
+                        &self.internal_candidate_recommendations(candidate_id),
+                    );
+                }
+            }
+            EntityKind::Region => {}
+            EntityKind::District => {
+                if let Some(district) = self.districts.get(&id) {
+                    self.assert_district_not_frozen(id, &district);
+                }
+            }
+            EntityKind::Candidate => self.assert_candidate_not_frozen(id),
+        }
+    }
+
+    /// Panics if replacing a stored candidate changes a result of a frozen campaign.
+    pub(crate) fn assert_candidate_not_frozen(&self, id: u64) {
+        if self.candidates.get(&id).is_some() {
+            self.assert_not_frozen(
+                "Candidate",
+                id,
+                &self.internal_candidate_recommendations(id),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::*;
+
+    /// District 1 with polling station 2 and party 2 without candidates,
+    /// campaign 1 recommends candidates 10 and 11 in district 1 and is frozen.
+    fn setup_frozen() -> VoteSmart {
+        let mut state = setup(1);
+        state.add_districts(vec![(2, district(2, Some(1)))]);
+        state.add_parties(vec![(2, "Other party".to_string())]);
+        state.add_detailed_recommendations(
+            vec![RecommendationInput {
+                campaign_id: 1,
+                district_id: 1,
+                candidate_ids: vec![10, 11],
+                seats: 1,
+                rationale: None,
+            }],
+            None,
+        );
+        state.freeze_campaign(1);
+        state
+    }
+
+    fn translation(entity: EntityKind, id: u64) -> TranslationInput {
+        TranslationInput {
+            entity,
+            id,
+            lang: "tt".to_string(),
+            title: "Title".to_string(),
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "row 0: campaign_id 1 is frozen")]
+    fn refuses_to_remove_frozen_recommendation() {
+        let mut state = setup_frozen();
+        state.remove_recommendations(vec![(1, 1)], None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Candidate 10 is referenced by frozen campaign 1")]
+    fn refuses_to_rename_frozen_candidate() {
+        let mut state = setup_frozen();
+        state.add_candidates(vec![(10, candidate(12, 1))]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Party 1 is referenced by frozen campaign 1")]
+    fn refuses_to_rename_party_of_frozen_candidate() {
+        let mut state = setup_frozen();
+        state.update_parties(vec![(1, "Renamed".to_string())]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Campaign is frozen")]
+    fn refuses_to_retitle_frozen_campaign() {
+        let mut state = setup_frozen();
+        state.update_campaign(
+            1,
+            CampaignUpdate {
+                title: Some("Renamed".to_string()),
+                election_date: None,
+                level: None,
+            },
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Parent unit 1 is referenced by frozen campaign 1")]
+    fn refuses_to_translate_unit_inheriting_frozen_recommendation() {
+        let mut state = setup_frozen();
+        state.add_translations(vec![translation(EntityKind::District, 2)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Party 1 is referenced by frozen campaign 1")]
+    fn refuses_to_translate_party_of_frozen_candidate() {
+        let mut state = setup_frozen();
+        state.add_translations(vec![translation(EntityKind::Party, 1)]);
+    }
+
+    #[test]
+    fn edits_entities_outside_frozen_results() {
+        let mut state = setup_frozen();
+        state.update_parties(vec![(2, "Renamed".to_string())]);
+        state.add_translations(vec![
+            translation(EntityKind::Party, 2),
+            translation(EntityKind::Region, 16),
+        ]);
+        assert_eq!(state.parties.get(&2).unwrap(), "Renamed");
+    }
+}
@@ -0,0 +1,495 @@
+use crate::validation::assert_no_invalid_rows;
+use crate::*;
+
+/// Number of referencing ids listed in the panic message.
+const MAX_REPORTED_REFERENCES: usize = 20;
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct CampaignUpdate {
+    pub title: Option<String>,
+    pub election_date: Option<U64>,
+    pub level: Option<CampaignLevel>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct RegionUpdate {
+    pub title: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct DistrictUpdate {
+    pub region_id: Option<u64>,
+    pub title: Option<String>,
+    pub unit_type: Option<UnitType>,
+    pub parent_id: Option<u64>,
+    /// Moves the unit to the top level of its region, can't be combined with `parent_id`.
+    pub top_level: Option<bool>,
+    pub address: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct CandidateUpdate {
+    pub title: Option<String>,
+    pub party_id: Option<u64>,
+    /// Withdrawn and disqualified candidates are replaced by fallbacks in `get_votesmart`,
+    /// so changing it for a recommended candidate also needs the curator role.
+    pub status: Option<CandidateStatus>,
+}
+
+#[near_bindgen]
+impl VoteSmart {
+    /// Status and publication time have their own methods.
+    pub fn update_campaign(&mut self, id: u64, update: CampaignUpdate) {
+        self.assert_role(Role::DataEditor);
+        let mut campaign = self.campaigns.get(&id).expect("Campaign not found");
+        self.assert_entity_not_frozen(EntityKind::Campaign, id);
+        if let Some(title) = update.title {
+            campaign.title = title;
+        }
+        if let Some(election_date) = update.election_date {
+            campaign.election_date = election_date;
+        }
+        if let Some(level) = update.level {
+            campaign.level = level;
+        }
+        let old = self.campaigns.insert(&id, &campaign);
+        self.internal_charge_storage(StorageCollection::Campaigns);
+        emit_change(EntityChange::new(
+            "campaign",
+            id,
+            old.as_ref(),
+            Some(&campaign),
+        ));
+    }
+
+    pub fn update_parties(&mut self, parties: Vec<(u64, String)>) {
+        self.assert_role(Role::DataEditor);
+        let parties = patch_rows(&self.parties, "party", parties, |party, title| {
+            *party = title
+        });
+        let changes = parties
+            .into_iter()
+            .map(|(id, party)| self.internal_put_party(id, party))
+            .collect();
+        emit_changes(changes);
+    }
+
+    pub fn update_regions(&mut self, regions: Vec<(u64, RegionUpdate)>) {
+        self.assert_role(Role::DataEditor);
+        let regions = patch_rows(&self.regions, "region", regions, |region, update| {
+            if let Some(title) = update.title {
+                region.title = title;
+            }
+        });
+        let changes = regions
+            .into_iter()
+            .map(|(id, region)| self.internal_put_region(id, region))
+            .collect();
+        emit_changes(changes);
+    }
+
+    pub fn update_districts(&mut self, districts: Vec<(u64, DistrictUpdate)>) {
+        self.assert_role(Role::DataEditor);
+        let districts = patch_rows(
+            &self.districts,
+            "district",
+            districts,
+            |district, update| {
+                if let Some(region_id) = update.region_id {
+                    district.region_id = region_id;
+                }
+                if let Some(title) = update.title {
+                    district.title = title;
+                }
+                if let Some(unit_type) = update.unit_type {
+                    district.unit_type = unit_type;
+                }
+                if update.top_level == Some(true) {
+                    assert!(
+                        update.parent_id.is_none(),
+                        "Pass either parent_id or top_level"
+                    );
+                    district.parent_id = None;
+                } else if update.parent_id.is_some() {
+                    district.parent_id = update.parent_id;
+                }
+                if update.address.is_some() {
+                    district.address = update.address;
+                }
+            },
+        );
+        self.assert_valid_districts(&districts, 0);
+        let changes = districts
+            .into_iter()
+            .map(|(id, district)| self.internal_put_district(id, district))
+            .collect();
+        emit_changes(changes);
+    }
+
+    pub fn update_candidates(&mut self, candidates: Vec<(u64, CandidateUpdate)>) {
+        self.assert_role(Role::DataEditor);
+        let candidates = patch_rows(
+            &self.candidates,
+            "candidate",
+            candidates,
+            |candidate, update| {
+                if let Some(title) = update.title {
+                    candidate.title = title;
+                }
+                if let Some(party_id) = update.party_id {
+                    candidate.party_id = party_id;
+                }
+                if let Some(status) = update.status {
+                    candidate.status = status;
+                }
+            },
+        );
+        self.assert_valid_candidates(&candidates, 0);
+        let changes = candidates
+            .into_iter()
+            .map(|(id, candidate)| self.internal_put_candidate(id, candidate))
+            .collect();
+        emit_changes(changes);
+    }
+
+    /// Refused while recommendations are given in the campaign, unless `cascade` is set.
+    pub fn remove_campaigns(&mut self, ids: Vec<u64>, cascade: Option<bool>) {
+        let cascade = self.assert_remove_access(cascade);
+        assert_existing_rows(&self.campaigns, "campaign", &ids);
+        let mut changes = vec![];
+        for id in ids {
+            assert!(!self.internal_is_frozen(id), "Campaign is frozen");
+            let recommendations = self.internal_campaign_recommendations(id);
+            assert_unreferenced(
+                "Campaign",
+                id,
+                "recommendations for districts",
+                recommendations.iter().map(|index| index.district_id),
+                cascade,
+            );
+            for index in recommendations {
+                self.internal_remove_recommendation_logged(&index, &mut changes);
+            }
+            let old = self.campaigns.remove(&id);
+            self.internal_charge_storage(StorageCollection::Campaigns);
+            changes.push(EntityChange::new("campaign", id, old.as_ref(), None));
+            self.internal_remove_translations(EntityKind::Campaign, id, &mut changes);
+        }
+        emit_changes(changes);
+    }
+
+    /// Refused while candidates belong to the party, unless `cascade` is set.
+    pub fn remove_parties(&mut self, ids: Vec<u64>, cascade: Option<bool>) {
+        let cascade = self.assert_remove_access(cascade);
+        assert_existing_rows(&self.parties, "party", &ids);
+        let mut changes = vec![];
+        for id in ids {
+            let candidate_ids = self.internal_party_candidates(id);
+            assert_unreferenced(
+                "Party",
+                id,
+                "candidates",
+                candidate_ids.iter().copied(),
+                cascade,
+            );
+            for candidate_id in candidate_ids {
+                self.internal_remove_candidate(candidate_id, cascade, &mut changes);
+            }
+            let old = self.parties.remove(&id);
+            self.internal_charge_storage(StorageCollection::Parties);
+            changes.push(EntityChange::new("party", id, old.as_ref(), None));
+            self.internal_remove_translations(EntityKind::Party, id, &mut changes);
+        }
+        emit_changes(changes);
+    }
+
+    /// Refused while districts belong to the region, unless `cascade` is set.
+    /// Cascading removes the whole subtree of each district.
+    pub fn remove_regions(&mut self, ids: Vec<u64>, cascade: Option<bool>) {
+        let cascade = self.assert_remove_access(cascade);
+        assert_existing_rows(&self.regions, "region", &ids);
+        let mut changes = vec![];
+        for id in ids {
+            let district_ids = self.internal_region_districts(id);
+            assert_unreferenced(
+                "Region",
+                id,
+                "districts",
+                district_ids.iter().copied(),
+                cascade,
+            );
+            for district_id in district_ids {
+                self.internal_remove_district(district_id, cascade, &mut changes);
+            }
+            let old = self.regions.remove(&id);
+            self.internal_charge_storage(StorageCollection::Regions);
+            changes.push(EntityChange::new("region", id, old.as_ref(), None));
+            self.internal_remove_translations(EntityKind::Region, id, &mut changes);
+        }
+        emit_changes(changes);
+    }
+
+    /// Refused while the district has child units or recommendations, unless `cascade` is set.
+    pub fn remove_districts(&mut self, ids: Vec<u64>, cascade: Option<bool>) {
+        let cascade = self.assert_remove_access(cascade);
+        assert_existing_rows(&self.districts, "district", &ids);
+        let mut changes = vec![];
+        for id in ids {
+            self.internal_remove_district(id, cascade, &mut changes);
+        }
+        emit_changes(changes);
+    }
+
+    /// Refused while the candidate is recommended, unless `cascade` is set.
+    pub fn remove_candidates(&mut self, ids: Vec<u64>, cascade: Option<bool>) {
+        let cascade = self.assert_remove_access(cascade);
+        assert_existing_rows(&self.candidates, "candidate", &ids);
+        let mut changes = vec![];
+        for id in ids {
+            self.internal_remove_candidate(id, cascade, &mut changes);
+        }
+        emit_changes(changes);
+    }
+
+    /// Cascading removal also drops recommendations, so it is reserved to owners.
+    fn assert_remove_access(&self, cascade: Option<bool>) -> bool {
+        let cascade = cascade.unwrap_or(false);
+        self.assert_role(if cascade {
+            Role::Owner
+        } else {
+            Role::DataEditor
+        });
+        cascade
+    }
+
+    fn internal_remove_district(
+        &mut self,
+        id: u64,
+        cascade: bool,
+        changes: &mut Vec<EntityChange>,
+    ) {
+        let recommendations = self.internal_district_recommendations(id);
+        self.assert_not_frozen("District", id, &recommendations);
+        let children = self.internal_district_children(id);
+        assert_unreferenced(
+            "District",
+            id,
+            "child units",
+            children.iter().copied(),
+            cascade,
+        );
+        assert_unreferenced(
+            "District",
+            id,
+            "recommendations in campaigns",
+            recommendations.iter().map(|index| index.campaign_id),
+            cascade,
+        );
+        for child_id in children {
+            self.internal_remove_district(child_id, cascade, changes);
+        }
+        for index in recommendations {
+            self.internal_remove_recommendation_logged(&index, changes);
+        }
+        if let Some(district) = self.districts.remove(&id) {
+            self.internal_unlink_district(id, &district);
+            self.internal_charge_storage(StorageCollection::Districts);
+            changes.push(EntityChange::new("district", id, Some(&district), None));
+        }
+        self.internal_remove_translations(EntityKind::District, id, changes);
+    }
+
+    fn internal_remove_candidate(
+        &mut self,
+        id: u64,
+        cascade: bool,
+        changes: &mut Vec<EntityChange>,
+    ) {
+        let recommendations = self.internal_candidate_recommendations(id);
+        self.assert_not_frozen("Candidate", id, &recommendations);
+        assert_unreferenced(
+            "Candidate",
+            id,
+            "recommendations in districts",
+            recommendations.iter().map(|index| index.district_id),
+            cascade,
+        );
+        for index in recommendations {
+            self.internal_remove_recommendation_logged(&index, changes);
+        }
+        if let Some(candidate) = self.candidates.remove(&id) {
+            self.internal_unlink_candidate(id, &candidate);
+            self.internal_charge_storage(StorageCollection::Candidates);
+            changes.push(EntityChange::new("candidate", id, Some(&candidate), None));
+        }
+        if let Some(profile) = self.candidate_profiles.remove(&id) {
+            self.internal_charge_storage(StorageCollection::CandidateProfiles);
+            changes.push(EntityChange::new(
+                "candidate_profile",
+                id,
+                Some(&profile),
+                None,
+            ));
+        }
+        self.internal_remove_translations(EntityKind::Candidate, id, changes);
+    }
+
+    /// Removal made directly by the caller as part of a cascade.
+    fn internal_remove_recommendation_logged(
+        &mut self,
+        index: &RecommendationIndex,
+        changes: &mut Vec<EntityChange>,
+    ) {
+        let origin = ChangeOrigin::predecessor();
+        if let Some(old) = self.internal_remove_recommendation(index, &origin) {
+            changes.push(EntityChange::recommendation(
+                index,
+                Some(&old),
+                None,
+                &origin,
+            ));
+        }
+    }
+}
+
+/// Applies partial updates to stored rows, rejecting the batch if any id is unknown.
+fn patch_rows<V, U>(
+    m: &UnorderedMap<u64, V>,
+    entity: &str,
+    rows: Vec<(u64, U)>,
+    apply: impl Fn(&mut V, U),
+) -> Vec<(u64, V)>
+where
+    V: BorshSerialize + BorshDeserialize,
+{
+    let mut errors = vec![];
+    let mut patched = vec![];
+    for (row, (id, update)) in rows.into_iter().enumerate() {
+        match m.get(&id) {
+            Some(mut value) => {
+                apply(&mut value, update);
+                patched.push((id, value));
+            }
+            None => errors.push(format!("row {} ({} {}): not found", row, entity, id)),
+        }
+    }
+    assert_no_invalid_rows(errors);
+    patched
+}
+
+pub(crate) fn assert_existing_rows<V>(m: &UnorderedMap<u64, V>, entity: &str, ids: &[u64])
+where
+    V: BorshSerialize + BorshDeserialize,
+{
+    let errors = ids
+        .iter()
+        .enumerate()
+        .filter(|(_, id)| m.get(id).is_none())
+        .map(|(row, id)| format!("row {} ({} {}): not found", row, entity, id))
+        .collect();
+    assert_no_invalid_rows(errors);
+}
+
+fn assert_unreferenced(
+    entity: &str,
+    id: u64,
+    referenced_by: &str,
+    ids: impl Iterator<Item = u64>,
+    cascade: bool,
+) {
+    if cascade {
+        return;
+    }
+    let ids: Vec<String> = ids.map(|id| id.to_string()).collect();
+    if ids.is_empty() {
+        return;
+    }
+    let mut listed = ids[..ids.len().min(MAX_REPORTED_REFERENCES)].join(", ");
+    if ids.len() > MAX_REPORTED_REFERENCES {
+        listed.push_str(&format!(
+            " and {} more",
+            ids.len() - MAX_REPORTED_REFERENCES
+        ));
+    }
+    env::panic(
+        format!(
+            "{} {} is referenced by {} {}, pass cascade to remove them too",
+            entity, id, referenced_by, listed
+        )
+        .as_bytes(),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::*;
+    use near_sdk::test_utils::accounts;
+
+    fn withdraw(state: &mut VoteSmart, caller: usize, candidate_id: u64) {
+        set_context(caller, 0);
+        state.update_candidates(vec![(
+            candidate_id,
+            CandidateUpdate {
+                title: None,
+                party_id: None,
+                status: Some(CandidateStatus::Withdrawn),
+            },
+        )]);
+    }
+
+    /// accounts(1) edits the data, accounts(2) also curates, three approvals are needed.
+    fn setup_with_roles() -> VoteSmart {
+        let mut state = setup(1);
+        state.add_detailed_recommendations(
+            vec![RecommendationInput {
+                campaign_id: 1,
+                district_id: 1,
+                candidate_ids: vec![10, 11],
+                seats: 1,
+                rationale: None,
+            }],
+            None,
+        );
+        state.add_candidates(vec![(12, candidate(12, 1))]);
+        state.grant_role(accounts(1), Role::DataEditor);
+        state.grant_role(accounts(2), Role::DataEditor);
+        state.grant_role(accounts(2), Role::RecommendationCurator);
+        state.set_approval_policy(ApprovalPolicy {
+            approvals_required: 3,
+            proposal_ttl: 100.into(),
+        });
+        state
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Status of a recommended candidate is changed by a recommendation curator"
+    )]
+    fn editor_cannot_withdraw_recommended_candidate() {
+        let mut state = setup_with_roles();
+        withdraw(&mut state, 1, 10);
+    }
+
+    #[test]
+    fn curator_withdraws_recommended_candidate() {
+        let mut state = setup_with_roles();
+        withdraw(&mut state, 2, 10);
+        let recommendation = state.get_votesmart(1, 1, None).unwrap();
+        assert!(recommendation.fallback_applied);
+        assert_eq!(recommendation.candidate_id, 11);
+    }
+
+    #[test]
+    fn editor_withdraws_other_candidates() {
+        let mut state = setup_with_roles();
+        withdraw(&mut state, 1, 12);
+        assert!(
+            state.get_candidate(12, None).unwrap().candidate.status == CandidateStatus::Withdrawn
+        );
+    }
+}
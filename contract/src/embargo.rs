@@ -0,0 +1,203 @@
+use crate::*;
+use near_sdk::serde_json::json;
+
+/// Publication times hide recommendations from view methods only, the
+/// contract state itself stays readable by anyone through the RPC.
+#[near_bindgen]
+impl VoteSmart {
+    /// Nanosecond timestamp before which the campaign's recommendations are hidden.
+    /// `None` publishes them at once.
+    pub fn set_campaign_publish_at(&mut self, campaign_id: u64, publish_at: Option<U64>) {
+        self.assert_role(Role::Owner);
+        let mut campaign = self
+            .campaigns
+            .get(&campaign_id)
+            .expect("Campaign not found");
+        campaign.publish_at = publish_at;
+        let old = self.campaigns.insert(&campaign_id, &campaign);
+        self.internal_charge_storage(StorageCollection::Campaigns);
+        emit_change(EntityChange::new(
+            "campaign",
+            campaign_id,
+            old.as_ref(),
+            Some(&campaign),
+        ));
+    }
+
+    /// Moves the publication time of a recommendation batch, see `add_recommendations`.
+    pub fn set_batch_publish_at(&mut self, proposal_id: u64, publish_at: Option<U64>) {
+        self.assert_role(Role::Owner);
+        assert!(proposal_id < self.next_proposal_id, "Proposal not found");
+        let old = match publish_at {
+            Some(publish_at) => self.batch_publish_at.insert(&proposal_id, &publish_at.0),
+            None => self.batch_publish_at.remove(&proposal_id),
+        };
+        self.internal_charge_storage(StorageCollection::Proposals);
+        if old.is_some() || publish_at.is_some() {
+            emit_change(EntityChange::new(
+                "batch_publish_at",
+                proposal_id,
+                old.map(U64).as_ref(),
+                publish_at.as_ref(),
+            ));
+        }
+    }
+
+    pub fn get_batch_publish_at(&self, proposal_id: u64) -> Option<U64> {
+        self.batch_publish_at.get(&proposal_id).map(U64)
+    }
+
+    /// Logs the values of the recommendations an embargoed batch wrote, once it is published.
+    /// Until then its events carry only the ids and the publication time. Anyone can call it,
+    /// recommendations replaced by a later batch since are skipped. Logs as many as fit
+    /// the log of a call and returns true once the whole batch is logged.
+    pub fn emit_published_batch(&mut self, proposal_id: u64) -> bool {
+        let mut indexes = self
+            .withheld_batches
+            .get(&proposal_id)
+            .expect("No withheld recommendations for the batch");
+        assert!(
+            self.internal_batch_embargo(proposal_id).is_none(),
+            "Batch is not published yet"
+        );
+
+        let placeholder = json!({ "publish_at": self.batch_publish_at.get(&proposal_id).map(U64) });
+        let published_change = |index: &RecommendationIndex| {
+            let record = self
+                .recommendations
+                .get(index)
+                .filter(|record| record.proposal_id == Some(proposal_id))?;
+            let history = self.recommendation_history.get(index)?;
+            let entry = history.get(history.len().checked_sub(1)?)?;
+            Some(EntityChange {
+                old: Some(placeholder.clone()),
+                editor: entry.editor,
+                ..EntityChange::new("recommendation", index, None, Some(&record))
+            })
+        };
+        let mut changes = vec![];
+        let mut log_length = 0;
+        let mut logged = 0;
+        for index in indexes.iter() {
+            if let Some(change) = published_change(index) {
+                if log_length + change.log_length() > MAX_EVENTS_DATA_LENGTH {
+                    break;
+                }
+                log_length += change.log_length();
+                changes.push(change);
+            }
+            logged += 1;
+        }
+        emit_changes(changes);
+
+        indexes.drain(..logged);
+        let done = indexes.is_empty();
+        if done {
+            self.withheld_batches.remove(&proposal_id);
+        } else {
+            self.withheld_batches.insert(&proposal_id, &indexes);
+        }
+        self.internal_charge_storage(StorageCollection::Proposals);
+        done
+    }
+}
+
+impl VoteSmart {
+    /// Draft campaigns and embargoed batches are not published.
+    pub(crate) fn internal_is_published(&self, campaign_id: u64, proposal_id: Option<u64>) -> bool {
+        let now = env::block_timestamp();
+        let campaign_published = self
+            .campaigns
+            .get(&campaign_id)
+            .map(|campaign| {
+                campaign.status != CampaignStatus::Draft
+                    && campaign
+                        .publish_at
+                        .is_none_or(|publish_at| now >= publish_at.0)
+            })
+            .unwrap_or(false);
+        let batch_published = proposal_id
+            .and_then(|proposal_id| self.batch_publish_at.get(&proposal_id))
+            .map(|publish_at| now >= publish_at)
+            .unwrap_or(true);
+        campaign_published && batch_published
+    }
+
+    /// Publication time of the batch if it is still in the future.
+    pub(crate) fn internal_batch_embargo(&self, proposal_id: u64) -> Option<u64> {
+        self.batch_publish_at
+            .get(&proposal_id)
+            .filter(|publish_at| env::block_timestamp() < *publish_at)
+    }
+
+    /// The latest recommendation whose batch is published. While a newer batch
+    /// is embargoed, the recommendation it replaced is returned.
+    pub(crate) fn internal_published_recommendation(
+        &self,
+        index: &RecommendationIndex,
+    ) -> Option<RecommendationRecord> {
+        if !self.internal_is_published(index.campaign_id, None) {
+            return None;
+        }
+        if let Some(record) = self.recommendations.get(index) {
+            if self.internal_is_published(index.campaign_id, record.proposal_id) {
+                return Some(record);
+            }
+        }
+
+        let history = self.recommendation_history.get(index)?;
+        (0..history.len())
+            .rev()
+            .map(|position| history.get(position).unwrap())
+            .find(|entry| self.internal_is_published(index.campaign_id, entry.proposal_id))
+            .and_then(|entry| entry.recommendation)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::*;
+
+    fn recommend(state: &mut VoteSmart, candidate_id: u64, publish_at: Option<u64>) -> u64 {
+        set_context(0, 0);
+        state.add_recommendations(vec![(1, 1, candidate_id)], publish_at.map(U64))
+    }
+
+    fn recommended(state: &VoteSmart) -> Option<u64> {
+        state
+            .get_votesmart(1, 1, None)
+            .map(|recommendation| recommendation.candidate_id)
+    }
+
+    #[test]
+    fn embargoed_campaign_is_hidden() {
+        let mut state = setup(1);
+        state.set_campaign_publish_at(1, Some(100.into()));
+        recommend(&mut state, 10, None);
+        assert_eq!(recommended(&state), None);
+        set_context(0, 100);
+        assert_eq!(recommended(&state), Some(10));
+    }
+
+    #[test]
+    fn embargoed_batch_shows_replaced_recommendation() {
+        let mut state = setup(1);
+        recommend(&mut state, 10, None);
+        let batch = recommend(&mut state, 11, Some(100));
+        assert_eq!(recommended(&state), Some(10));
+        set_context(0, 100);
+        assert_eq!(recommended(&state), Some(11));
+        assert!(state.emit_published_batch(batch));
+        assert_eq!(events()[0]["data"][0]["new"]["candidate_ids"], json!([11]));
+    }
+
+    #[test]
+    #[should_panic(expected = "Batch is not published yet")]
+    fn embargoed_batch_is_not_logged_early() {
+        let mut state = setup(1);
+        let batch = recommend(&mut state, 10, Some(100));
+        set_context(0, 99);
+        state.emit_published_batch(batch);
+    }
+}
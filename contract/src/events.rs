@@ -0,0 +1,225 @@
+use crate::*;
+use near_sdk::serde_json::{self, json, Value};
+
+/// NEP-297 `standard` and `version` fields of the emitted events.
+pub(crate) const EVENT_STANDARD: &str = "votesmart";
+pub(crate) const EVENT_VERSION: &str = "1.0.0";
+
+/// Total length of the logs of a call allowed by the protocol.
+const MAX_TOTAL_LOG_LENGTH: usize = 16 * 1024;
+
+/// Length left for the `data` arrays, after the envelopes of the three event kinds.
+pub const MAX_EVENTS_DATA_LENGTH: usize = MAX_TOTAL_LOG_LENGTH - 3 * 100;
+
+/// One changed entity in the `data` array of an event.
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct EntityChange {
+    pub entity: &'static str,
+    pub id: Value,
+    /// None for created entities.
+    pub old: Option<Value>,
+    /// None for removed entities.
+    pub new: Option<Value>,
+    pub editor: AccountId,
+}
+
+impl EntityChange {
+    /// Change made by the caller.
+    pub(crate) fn new<I: Serialize, V: Serialize>(
+        entity: &'static str,
+        id: I,
+        old: Option<&V>,
+        new: Option<&V>,
+    ) -> Self {
+        Self {
+            entity,
+            id: to_value(&id),
+            old: old.map(to_value),
+            new: new.map(to_value),
+            editor: env::predecessor_account_id(),
+        }
+    }
+
+    pub(crate) fn recommendation(
+        index: &RecommendationIndex,
+        old: Option<&RecommendationRecord>,
+        new: Option<&RecommendationRecord>,
+        origin: &ChangeOrigin,
+    ) -> Self {
+        Self {
+            editor: origin.editor.clone(),
+            ..Self::new("recommendation", index, old, new)
+        }
+    }
+
+    /// The changes of a proposal are logged once, when it is created.
+    pub(crate) fn proposal(
+        id: u64,
+        old: Option<&RecommendationProposal>,
+        new: Option<&RecommendationProposal>,
+    ) -> Self {
+        let mut change = Self::new("recommendation_proposal", id, old, new);
+        if old.is_some() {
+            for value in change.old.iter_mut().chain(change.new.iter_mut()) {
+                if let Some(fields) = value.as_object_mut() {
+                    fields.remove("changes");
+                }
+            }
+        }
+        change
+    }
+
+    /// Replaces the values of a recommendation from an embargoed batch by the batch
+    /// publication time. The values are logged by `emit_published_batch`.
+    pub(crate) fn withheld(mut self, publish_at: u64) -> Self {
+        let placeholder = json!({ "publish_at": U64(publish_at) });
+        for value in self.old.iter_mut().chain(self.new.iter_mut()) {
+            *value = placeholder.clone();
+        }
+        self
+    }
+
+    /// Keeps only the type and the ids of the changes of an embargoed proposal.
+    pub(crate) fn withheld_changes(mut self) -> Self {
+        for value in self.old.iter_mut().chain(self.new.iter_mut()) {
+            if let Some(changes) = value.get_mut("changes").and_then(Value::as_array_mut) {
+                for change in changes.iter_mut() {
+                    *change = json!({
+                        "type": change["type"],
+                        "campaign_id": change["campaign_id"],
+                        "district_id": change["district_id"],
+                    });
+                }
+            }
+        }
+        self
+    }
+
+    /// Length of the change in the `data` array of the log.
+    pub(crate) fn log_length(&self) -> usize {
+        to_value(self).to_string().len() + 1
+    }
+
+    fn event(&self) -> &'static str {
+        match (&self.old, &self.new) {
+            (None, _) => "create",
+            (_, None) => "remove",
+            _ => "update",
+        }
+    }
+}
+
+/// Logs the changes as `EVENT_JSON:` lines, one event per kind of change.
+/// When the values don't fit the log size the protocol allows to a call, only the ids
+/// are logged, grouped by entity. Calls whose ids don't fit either are refused.
+pub(crate) fn emit_changes(changes: Vec<EntityChange>) {
+    let mut logs = event_logs(&changes, |data| json!(data));
+    if logs_length(&logs) > MAX_TOTAL_LOG_LENGTH {
+        logs = event_logs(&changes, compact_data);
+        assert!(
+            logs_length(&logs) <= MAX_TOTAL_LOG_LENGTH,
+            "Too many changes to log in one call, split it into smaller calls"
+        );
+    }
+    for log in logs {
+        env::log(log.as_bytes());
+    }
+}
+
+pub(crate) fn emit_change(change: EntityChange) {
+    emit_changes(vec![change]);
+}
+
+fn event_logs(changes: &[EntityChange], data: impl Fn(Vec<&EntityChange>) -> Value) -> Vec<String> {
+    ["create", "update", "remove"]
+        .iter()
+        .filter_map(|event| {
+            let changes: Vec<&EntityChange> = changes
+                .iter()
+                .filter(|change| change.event() == *event)
+                .collect();
+            if changes.is_empty() {
+                return None;
+            }
+            let log = json!({
+                "standard": EVENT_STANDARD,
+                "version": EVENT_VERSION,
+                "event": event,
+                "data": data(changes),
+            });
+            Some(format!("EVENT_JSON:{}", log))
+        })
+        .collect()
+}
+
+fn logs_length(logs: &[String]) -> usize {
+    logs.iter().map(String::len).sum()
+}
+
+/// `{"entity", "ids", "editor"}` entries, one per entity and editor.
+fn compact_data(changes: Vec<&EntityChange>) -> Value {
+    let mut groups: Vec<(&str, &AccountId, Vec<&Value>)> = vec![];
+    for change in changes {
+        match groups
+            .iter_mut()
+            .find(|(entity, editor, _)| *entity == change.entity && **editor == change.editor)
+        {
+            Some((_, _, ids)) => ids.push(&change.id),
+            None => groups.push((change.entity, &change.editor, vec![&change.id])),
+        }
+    }
+    groups
+        .into_iter()
+        .map(|(entity, editor, ids)| json!({ "entity": entity, "ids": ids, "editor": editor }))
+        .collect()
+}
+
+fn to_value<T: Serialize>(value: &T) -> Value {
+    serde_json::to_value(value).expect("Failed to serialize event")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::*;
+
+    #[test]
+    fn logs_values_of_small_calls() {
+        let mut state = setup(1);
+        set_context(0, 0);
+        state.update_parties(vec![(1, "Party 2".to_string())]);
+        let events = events();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0]["event"], "update");
+        assert_eq!(events[0]["data"][0]["old"], "Party");
+        assert_eq!(events[0]["data"][0]["new"], "Party 2");
+    }
+
+    #[test]
+    fn logs_ids_of_bulk_calls() {
+        let mut state = setup(0);
+        let districts = || (1..=120).map(|id| (id, district(id, None))).collect();
+        set_context(0, 0);
+        state.add_districts(districts());
+        let created = events();
+        assert_eq!(created[0]["event"], "create");
+        assert_eq!(created[0]["data"][0]["entity"], "district");
+        assert_eq!(created[0]["data"][0]["ids"].as_array().unwrap().len(), 120);
+        assert!(created[0]["data"][0].get("new").is_none());
+
+        set_context(0, 0);
+        state.add_districts(districts());
+        assert_eq!(events()[0]["event"], "update");
+    }
+
+    #[test]
+    fn cascade_removal_of_a_large_region() {
+        let mut state = setup(50);
+        set_context(0, 0);
+        state.add_recommendations((1..=50).map(|id| (1, id, 10)).collect(), None);
+        set_context(0, 0);
+        state.remove_regions(vec![16], Some(true));
+        assert!(events()[0]["data"][0].get("ids").is_some());
+        assert!(state.get_districts(None, None, None).is_empty());
+    }
+}
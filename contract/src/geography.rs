@@ -0,0 +1,148 @@
+use crate::*;
+
+#[derive(
+    BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Copy, PartialEq, Default,
+)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(rename_all = "snake_case")]
+pub enum UnitType {
+    /// Stored in `regions`, the root level of the tree.
+    Region,
+    #[default]
+    District,
+    Municipality,
+    PollingStation,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct AdminUnit {
+    pub id: u64,
+    pub unit_type: UnitType,
+    pub title: String,
+}
+
+#[near_bindgen]
+impl VoteSmart {
+    /// Units whose `parent_id` is the given district. Top level districts are listed by `get_districts_by_region_page`.
+    pub fn get_children(
+        &self,
+        parent_id: u64,
+        cursor: Option<u64>,
+        limit: Option<u64>,
+        lang: Option<String>,
+    ) -> Page<(u64, District)> {
+        self.internal_districts_page(self.unit_children.get(&parent_id), cursor, limit, &lang)
+    }
+
+    /// Parent units of the district, the nearest first and the region last.
+    pub fn get_ancestors(&self, unit_id: u64, lang: Option<String>) -> Vec<AdminUnit> {
+        let district = match self.districts.get(&unit_id) {
+            Some(district) => district,
+            None => return vec![],
+        };
+        let mut ancestors: Vec<AdminUnit> = self
+            .internal_district_ancestors(&district)
+            .into_iter()
+            .map(|(id, district)| AdminUnit {
+                id,
+                unit_type: district.unit_type,
+                title: self.internal_localize(EntityKind::District, id, district.title, &lang),
+            })
+            .collect();
+        if let Some(region) = self.regions.get(&district.region_id) {
+            ancestors.push(AdminUnit {
+                id: district.region_id,
+                unit_type: UnitType::Region,
+                title: self.internal_localize(
+                    EntityKind::Region,
+                    district.region_id,
+                    region.title,
+                    &lang,
+                ),
+            });
+        }
+        ancestors
+    }
+}
+
+impl VoteSmart {
+    /// Stores the district and moves it to its parent's children,
+    /// or to the region's districts for top level units. Returns the previous value.
+    pub(crate) fn internal_insert_district(
+        &mut self,
+        id: u64,
+        district: &District,
+    ) -> Option<District> {
+        let previous = self.districts.insert(&id, district);
+        let moved = previous.as_ref().is_none_or(|previous| {
+            previous.parent_id != district.parent_id || previous.region_id != district.region_id
+        });
+        if !moved {
+            return previous;
+        }
+        if let Some(ref previous) = previous {
+            self.internal_unlink_district(id, previous);
+        }
+        match district.parent_id {
+            Some(parent_id) => {
+                let mut children = self.unit_children.get(&parent_id).unwrap_or_else(|| {
+                    UnorderedSet::new(StorageKey::UnitChildrenByParent { parent_id })
+                });
+                children.insert(&id);
+                self.unit_children.insert(&parent_id, &children);
+            }
+            None => {
+                let region_id = district.region_id;
+                let mut districts = self.region_districts.get(&region_id).unwrap_or_else(|| {
+                    UnorderedSet::new(StorageKey::RegionDistrictsByRegion { region_id })
+                });
+                districts.insert(&id);
+                self.region_districts.insert(&region_id, &districts);
+            }
+        }
+        previous
+    }
+
+    pub(crate) fn internal_unlink_district(&mut self, id: u64, district: &District) {
+        let (index, key) = match district.parent_id {
+            Some(parent_id) => (&mut self.unit_children, parent_id),
+            None => (&mut self.region_districts, district.region_id),
+        };
+        if let Some(mut ids) = index.get(&key) {
+            ids.remove(&id);
+            if ids.is_empty() {
+                index.remove(&key);
+            } else {
+                index.insert(&key, &ids);
+            }
+        }
+    }
+
+    /// Top level districts of the region.
+    pub(crate) fn internal_region_districts(&self, region_id: u64) -> Vec<u64> {
+        self.region_districts
+            .get(&region_id)
+            .map(|districts| districts.to_vec())
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn internal_district_children(&self, id: u64) -> Vec<u64> {
+        self.unit_children
+            .get(&id)
+            .map(|children| children.to_vec())
+            .unwrap_or_default()
+    }
+
+    /// Parent districts, the nearest first.
+    pub(crate) fn internal_district_ancestors(&self, district: &District) -> Vec<(u64, District)> {
+        let mut ancestors = vec![];
+        let mut parent_id = district.parent_id;
+        while let Some(id) = parent_id {
+            let parent = self.districts.get(&id).expect("Parent unit not found");
+            parent_id = parent.parent_id;
+            ancestors.push((id, parent));
+        }
+        ancestors
+    }
+}
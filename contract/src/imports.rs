@@ -0,0 +1,232 @@
+use crate::*;
+use near_sdk::Gas;
+
+/// Gas kept for logging the events and saving the state after the last imported row.
+const IMPORT_GAS_RESERVE: Gas = 20_000_000_000_000;
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ImportProgress {
+    /// Rows written by this call.
+    pub applied: u64,
+    /// Position in the whole dataset of the first row not written yet.
+    pub next_row: U64,
+    /// Pass it with the rows from `next_row` on to continue, `None` when all rows are written.
+    pub resume_token: Option<String>,
+}
+
+/// The `import_*` methods check and write the rows of `add_*` one by one and stop before
+/// the gas or the event log runs out. The next call takes the rows left and the resume token,
+/// which is bound to the first of them, so rows sent out of order are refused.
+#[near_bindgen]
+impl VoteSmart {
+    pub fn import_parties(
+        &mut self,
+        parties: Vec<(u64, String)>,
+        resume_token: Option<String>,
+    ) -> ImportProgress {
+        self.assert_role(Role::DataEditor);
+        self.internal_import(parties, resume_token, vec![], |contract, _, (id, party)| {
+            contract.internal_put_party(id, party)
+        })
+    }
+
+    pub fn import_regions(
+        &mut self,
+        regions: Vec<(u64, Region)>,
+        resume_token: Option<String>,
+    ) -> ImportProgress {
+        self.assert_role(Role::DataEditor);
+        self.internal_import(
+            regions,
+            resume_token,
+            vec![],
+            |contract, _, (id, region)| contract.internal_put_region(id, region),
+        )
+    }
+
+    /// Parent units have to come before their children, unlike in `add_districts`.
+    pub fn import_districts(
+        &mut self,
+        districts: Vec<(u64, District)>,
+        resume_token: Option<String>,
+    ) -> ImportProgress {
+        self.assert_role(Role::DataEditor);
+        self.internal_import(
+            districts,
+            resume_token,
+            vec![],
+            |contract, row, district| {
+                contract.assert_valid_districts(std::slice::from_ref(&district), row);
+                let (id, district) = district;
+                contract.internal_put_district(id, district)
+            },
+        )
+    }
+
+    pub fn import_candidates(
+        &mut self,
+        candidates: Vec<(u64, Candidate)>,
+        resume_token: Option<String>,
+    ) -> ImportProgress {
+        self.assert_role(Role::DataEditor);
+        self.internal_import(
+            candidates,
+            resume_token,
+            vec![],
+            |contract, row, candidate| {
+                contract.assert_valid_candidates(std::slice::from_ref(&candidate), row);
+                let (id, candidate) = candidate;
+                contract.internal_put_candidate(id, candidate)
+            },
+        )
+    }
+
+    /// Writes the recommendations without a proposal, so it is refused while the policy
+    /// needs more than one approval. Each call is a batch of its own, `publish_at` applies
+    /// to the rows it writes and has to be passed again when resuming.
+    pub fn import_recommendations(
+        &mut self,
+        recommendations: Vec<RecommendationInput>,
+        publish_at: Option<U64>,
+        resume_token: Option<String>,
+    ) -> ImportProgress {
+        self.assert_role(Role::RecommendationCurator);
+        assert_eq!(
+            self.approval_policy.approvals_required, 1,
+            "Batches need several approvals, use add_detailed_recommendations"
+        );
+        let mut changes = vec![];
+        let proposal_id = self.internal_new_batch(publish_at, &mut changes);
+        let embargo = self.internal_batch_embargo(proposal_id);
+        let origin = ChangeOrigin {
+            editor: env::predecessor_account_id(),
+            proposal_id: Some(proposal_id),
+            approvals: vec![env::predecessor_account_id()],
+        };
+        let mut withheld = vec![];
+        let progress = self.internal_import(
+            recommendations,
+            resume_token,
+            changes,
+            |contract, row, input| {
+                let change = RecommendationChange::Set(input);
+                contract.assert_valid_recommendation_changes(std::slice::from_ref(&change), row);
+                withheld.push(change.index());
+                contract
+                    .internal_apply_recommendation(&change, &origin, embargo)
+                    .expect("Set is always logged")
+            },
+        );
+        if embargo.is_some() {
+            self.internal_withhold_batch(proposal_id, withheld);
+        }
+        progress
+    }
+}
+
+impl VoteSmart {
+    /// Writes the rows while the gas and the log length left cover the most expensive row
+    /// written so far. `changes` made before the rows are logged with them.
+    fn internal_import<T: BorshSerialize>(
+        &mut self,
+        rows: Vec<T>,
+        resume_token: Option<String>,
+        mut changes: Vec<EntityChange>,
+        mut put: impl FnMut(&mut Self, usize, T) -> EntityChange,
+    ) -> ImportProgress {
+        let start = match resume_token {
+            Some(token) => {
+                let (start, hash) = parse_resume_token(&token);
+                assert!(
+                    rows.first().is_some_and(|row| row_hash(row) == hash),
+                    "Rows do not continue the import at the resume token"
+                );
+                start
+            }
+            None => 0,
+        };
+        let mut applied = 0;
+        let mut resume_token = None;
+        let (mut row_gas, mut row_log_length) = (0, 0);
+        let mut log_length: usize = changes.iter().map(EntityChange::log_length).sum();
+        for (position, row) in (start..).zip(rows) {
+            let used_gas = env::used_gas();
+            if env::prepaid_gas().saturating_sub(used_gas) < IMPORT_GAS_RESERVE + row_gas
+                || log_length + row_log_length > MAX_EVENTS_DATA_LENGTH
+            {
+                resume_token = Some(format!("{}:{}", position, row_hash(&row)));
+                break;
+            }
+            let change = put(self, position, row);
+            row_gas = row_gas.max(env::used_gas() - used_gas);
+            row_log_length = row_log_length.max(change.log_length());
+            log_length += change.log_length();
+            changes.push(change);
+            applied += 1;
+        }
+        assert!(
+            applied > 0 || resume_token.is_none(),
+            "Not enough gas to import a row"
+        );
+        emit_changes(changes);
+        ImportProgress {
+            applied: applied as u64,
+            next_row: ((start + applied) as u64).into(),
+            resume_token,
+        }
+    }
+}
+
+/// `<position>:<hash>`, the position of the next row in the dataset and the hash of the row.
+fn parse_resume_token(token: &str) -> (usize, String) {
+    let mut parts = token.splitn(2, ':');
+    match (parts.next().map(str::parse), parts.next()) {
+        (Some(Ok(position)), Some(hash)) => (position, hash.to_string()),
+        _ => env::panic(b"Invalid resume token"),
+    }
+}
+
+/// Hex SHA-256 of the Borsh serialized row.
+fn row_hash<T: BorshSerialize>(row: &T) -> String {
+    let bytes = row.try_to_vec().expect("Failed to serialize row");
+    env::sha256(&bytes)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::*;
+
+    fn party(id: u64) -> (u64, String) {
+        (id, format!("Party {}", id))
+    }
+
+    #[test]
+    fn import_continues_at_resume_token() {
+        let mut state = setup(0);
+        let token = format!("1:{}", row_hash(&party(3)));
+        let progress = state.import_parties(vec![party(3), party(4)], Some(token));
+        assert_eq!(progress.applied, 2);
+        assert_eq!(progress.next_row.0, 3);
+        assert!(progress.resume_token.is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "Rows do not continue the import at the resume token")]
+    fn import_refuses_other_rows() {
+        let mut state = setup(0);
+        let token = format!("1:{}", row_hash(&party(3)));
+        state.import_parties(vec![party(4)], Some(token));
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid resume token")]
+    fn import_refuses_invalid_token() {
+        let mut state = setup(0);
+        state.import_parties(vec![party(3)], Some("x:00".to_string()));
+    }
+}
@@ -1,8 +1,44 @@
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
-use near_sdk::collections::{LookupMap, UnorderedMap};
-use near_sdk::json_types::ValidAccountId;
+use near_sdk::collections::{LookupMap, UnorderedMap, UnorderedSet, Vector};
+use near_sdk::json_types::{ValidAccountId, U64};
 use near_sdk::serde::{Deserialize, Serialize};
 use near_sdk::{env, near_bindgen, setup_alloc, AccountId, BorshStorageKey, PanicOnDefault};
+use std::collections::HashMap;
+
+use crate::migration::{write_state_version, Migration, STATE_VERSION};
+
+pub use crate::access::*;
+pub use crate::campaigns::*;
+pub use crate::editing::*;
+pub use crate::events::*;
+pub use crate::geography::*;
+pub use crate::imports::*;
+pub use crate::merkle::*;
+pub use crate::pagination::*;
+pub use crate::profiles::*;
+pub use crate::proposals::*;
+pub use crate::recommendations::*;
+pub use crate::storage::*;
+pub use crate::translations::*;
+
+mod access;
+mod campaigns;
+mod editing;
+mod embargo;
+mod events;
+mod geography;
+mod imports;
+mod merkle;
+mod migration;
+mod pagination;
+mod profiles;
+mod proposals;
+mod recommendations;
+mod storage;
+#[cfg(test)]
+mod testing;
+mod translations;
+mod validation;
 
 setup_alloc!();
 
@@ -11,11 +47,41 @@ setup_alloc!();
 pub struct VoteSmart {
     master_account_id: AccountId,
     parties: UnorderedMap<u64, String>,
-    campaigns: UnorderedMap<u64, String>,
+    campaigns: UnorderedMap<u64, Campaign>,
     regions: UnorderedMap<u64, Region>,
     districts: UnorderedMap<u64, District>,
     candidates: UnorderedMap<u64, Candidate>,
-    recommendations: LookupMap<RecommendationIndex, u64>,
+    recommendations: LookupMap<RecommendationIndex, RecommendationRecord>,
+    roles: UnorderedMap<AccountId, Vec<Role>>,
+    pending_admin: Option<PendingAdmin>,
+    admin_transfer_delay: u64,
+    pending_admin_transfer_delay: Option<PendingAdminTransferDelay>,
+    recommendation_proposals: UnorderedMap<u64, RecommendationProposal>,
+    next_proposal_id: u64,
+    approval_policy: ApprovalPolicy,
+    candidate_recommendations: LookupMap<u64, Vec<RecommendationIndex>>,
+    recommended_districts: LookupMap<u64, UnorderedSet<u64>>,
+    recommendation_history: LookupMap<RecommendationIndex, Vector<RecommendationHistoryEntry>>,
+    batch_publish_at: LookupMap<u64, u64>,
+    /// Recommendations of embargoed batches whose values are not logged yet.
+    withheld_batches: LookupMap<u64, Vec<RecommendationIndex>>,
+    merkle_leaf_positions: LookupMap<RecommendationIndex, u64>,
+    merkle_trees: LookupMap<u64, MerkleTree>,
+    merkle_nodes: LookupMap<MerkleNodeIndex, MerkleHash>,
+    unit_children: LookupMap<u64, UnorderedSet<u64>>,
+    region_districts: LookupMap<u64, UnorderedSet<u64>>,
+    party_candidates: LookupMap<u64, UnorderedSet<u64>>,
+    candidate_profiles: LookupMap<u64, CandidateProfile>,
+    translations: LookupMap<(EntityKind, u64), HashMap<String, String>>,
+    default_language: String,
+    /// Entities with at least one translation.
+    translated_entities: u64,
+    /// Rows of the previous layout not moved yet, see `migrate`.
+    migration: Option<Migration>,
+    /// Bytes used by each collection, see `get_storage_stats`.
+    collection_storage: HashMap<StorageCollection, u64>,
+    #[borsh_skip]
+    storage_meter: StorageMeter,
 }
 
 #[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
@@ -29,6 +95,14 @@ pub struct Region {
 pub struct District {
     pub region_id: u64,
     pub title: String,
+    #[serde(default)]
+    pub unit_type: UnitType,
+    /// Enclosing unit of the same region, `None` for units directly under the region.
+    #[serde(default)]
+    pub parent_id: Option<u64>,
+    /// Polling station address.
+    #[serde(default)]
+    pub address: Option<String>,
 }
 
 #[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
@@ -36,6 +110,20 @@ pub struct District {
 pub struct Candidate {
     pub title: String,
     pub party_id: u64,
+    #[serde(default)]
+    pub status: CandidateStatus,
+}
+
+#[derive(
+    BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Copy, PartialEq, Default,
+)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(rename_all = "snake_case")]
+pub enum CandidateStatus {
+    #[default]
+    Active,
+    Withdrawn,
+    Disqualified,
 }
 
 #[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
@@ -48,11 +136,36 @@ pub struct Party {
 #[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
 #[serde(crate = "near_sdk::serde")]
 pub struct Recommendation {
+    /// First recommended candidate, kept for single-mandate clients.
+    pub candidate_id: u64,
+    pub title: String,
+    pub party_id: u64,
+    /// None if the party is not found.
+    pub party: Option<String>,
+    /// All recommended candidates in rank order, at most `seats` of them.
+    pub candidates: Vec<RecommendedCandidate>,
+    pub seats: u32,
+    /// A recommended candidate is not active and a fallback candidate is returned.
+    pub fallback_applied: bool,
+    pub campaign_title: String,
+    pub district_title: String,
+    pub rationale: Option<String>,
+    /// Block timestamp of the last change of the recommendation.
+    pub updated_at: U64,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct RecommendedCandidate {
+    pub candidate_id: u64,
     pub title: String,
-    pub party: String,
+    pub party_id: u64,
+    /// None if the party is not found.
+    pub party: Option<String>,
 }
 
-#[derive(BorshDeserialize, BorshSerialize)]
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
 pub struct RecommendationIndex {
     pub campaign_id: u64,
     pub district_id: u64,
@@ -67,6 +180,43 @@ pub enum StorageKey {
     Districts,
     Candidates,
     Recommendations,
+    Roles,
+    RecommendationProposals,
+    CandidateRecommendations,
+    RecommendedDistricts,
+    RecommendedDistrictsByCampaign {
+        campaign_id: u64,
+    },
+    RecommendationHistory,
+    RecommendationHistoryByIndex {
+        campaign_id: u64,
+        district_id: u64,
+    },
+    BatchPublishAt,
+    MerkleLeafPositions,
+    MerkleTrees,
+    MerkleNodes,
+    UnitChildren,
+    UnitChildrenByParent {
+        parent_id: u64,
+    },
+    RegionDistricts,
+    RegionDistrictsByRegion {
+        region_id: u64,
+    },
+    CandidateProfiles,
+    Translations,
+    StateVersion,
+    /// Collections whose rows changed, the previous keys hold rows until they are migrated.
+    CampaignsV2,
+    DistrictsV2,
+    CandidatesV2,
+    RecommendationsV2,
+    WithheldBatches,
+    PartyCandidates,
+    PartyCandidatesByParty {
+        party_id: u64,
+    },
 }
 
 #[near_bindgen]
@@ -78,19 +228,11 @@ impl VoteSmart {
         } else {
             env::predecessor_account_id()
         };
-
-        Self {
-            master_account_id,
-            parties: UnorderedMap::new(StorageKey::Parties),
-            campaigns: UnorderedMap::new(StorageKey::Campaigns),
-            regions: UnorderedMap::new(StorageKey::Regions),
-            districts: UnorderedMap::new(StorageKey::Districts),
-            candidates: UnorderedMap::new(StorageKey::Candidates),
-            recommendations: LookupMap::new(StorageKey::Recommendations),
-        }
+        Self::internal_new(master_account_id)
     }
 
     pub(crate) fn assert_access(&self) {
+        self.assert_not_migrating();
         assert_eq!(
             env::predecessor_account_id(),
             self.master_account_id,
@@ -98,132 +240,300 @@ impl VoteSmart {
         );
     }
 
-    pub fn set_master_account_id(&mut self, admin_id: ValidAccountId) {
-        self.assert_access();
-        self.master_account_id = admin_id.into();
-    }
-
-    pub fn add_campaign(&mut self, id: u64, title: String) {
-        self.assert_access();
-        self.campaigns.insert(&id, &title);
+    /// New campaigns start as drafts, see `set_campaign_status`.
+    pub fn add_campaign(
+        &mut self,
+        id: u64,
+        title: String,
+        election_date: U64,
+        level: CampaignLevel,
+    ) {
+        self.assert_role(Role::DataEditor);
+        assert!(self.campaigns.get(&id).is_none(), "Campaign already exists");
+        let campaign = Campaign {
+            title,
+            election_date,
+            level,
+            status: CampaignStatus::Draft,
+            publish_at: None,
+            frozen_at: None,
+        };
+        self.campaigns.insert(&id, &campaign);
+        self.internal_charge_storage(StorageCollection::Campaigns);
+        emit_change(EntityChange::new("campaign", id, None, Some(&campaign)));
     }
 
-    pub fn get_campaigns(&self, from_index: Option<u64>, limit: Option<u64>) -> Vec<(u64, String)> {
-        unordered_map_pagination(&self.campaigns, from_index, limit)
+    pub fn get_campaigns(
+        &self,
+        from_index: Option<u64>,
+        limit: Option<u64>,
+        status: Option<CampaignStatus>,
+        lang: Option<String>,
+    ) -> Vec<(u64, Campaign)> {
+        let keys = self.campaigns.keys_as_vector();
+        let values = self.campaigns.values_as_vector();
+        let from_index = from_index.unwrap_or(0);
+        let limit = limit.unwrap_or(keys.len());
+        let campaigns = (from_index..std::cmp::min(keys.len(), limit))
+            .map(|index| (keys.get(index).unwrap(), values.get(index).unwrap()))
+            .filter(|(_, campaign)| status.is_none_or(|status| campaign.status == status))
+            .collect();
+        self.internal_localize_all(EntityKind::Campaign, campaigns, &lang)
     }
 
     pub fn add_parties(&mut self, parties: Vec<(u64, String)>) {
-        self.assert_access();
-        for data in parties {
-            self.parties.insert(&data.0, &data.1);
-        }
+        self.assert_role(Role::DataEditor);
+        let changes = parties
+            .into_iter()
+            .map(|(id, party)| self.internal_put_party(id, party))
+            .collect();
+        emit_changes(changes);
     }
 
-    pub fn get_parties(&self, from_index: Option<u64>, limit: Option<u64>) -> Vec<(u64, String)> {
-        unordered_map_pagination(&self.parties, from_index, limit)
+    pub fn get_parties(
+        &self,
+        from_index: Option<u64>,
+        limit: Option<u64>,
+        lang: Option<String>,
+    ) -> Vec<(u64, String)> {
+        let parties = unordered_map_pagination(&self.parties, from_index, limit);
+        self.internal_localize_all(EntityKind::Party, parties, &lang)
     }
 
     pub fn add_regions(&mut self, regions: Vec<(u64, Region)>) {
-        self.assert_access();
-        for data in regions {
-            self.regions.insert(&data.0, &data.1);
-        }
+        self.assert_role(Role::DataEditor);
+        let changes = regions
+            .into_iter()
+            .map(|(id, region)| self.internal_put_region(id, region))
+            .collect();
+        emit_changes(changes);
     }
 
-    pub fn get_regions(&self, from_index: Option<u64>, limit: Option<u64>) -> Vec<(u64, Region)> {
-        unordered_map_pagination(&self.regions, from_index, limit)
+    pub fn get_regions(
+        &self,
+        from_index: Option<u64>,
+        limit: Option<u64>,
+        lang: Option<String>,
+    ) -> Vec<(u64, Region)> {
+        let regions = unordered_map_pagination(&self.regions, from_index, limit);
+        self.internal_localize_all(EntityKind::Region, regions, &lang)
     }
 
     pub fn add_districts(&mut self, districts: Vec<(u64, District)>) {
-        self.assert_access();
-        for data in districts {
-            self.districts.insert(&data.0, &data.1);
-        }
+        self.assert_role(Role::DataEditor);
+        self.assert_valid_districts(&districts, 0);
+        let changes = districts
+            .into_iter()
+            .map(|(id, district)| self.internal_put_district(id, district))
+            .collect();
+        emit_changes(changes);
     }
 
     pub fn get_districts(
         &self,
         from_index: Option<u64>,
         limit: Option<u64>,
+        lang: Option<String>,
     ) -> Vec<(u64, District)> {
-        unordered_map_pagination(&self.districts, from_index, limit)
+        let districts = unordered_map_pagination(&self.districts, from_index, limit);
+        self.internal_localize_all(EntityKind::District, districts, &lang)
     }
 
+    /// Units of the region, nested ones included, among the districts from `from_index` to
+    /// `limit` of all regions. Kept for old clients, use `get_districts_by_region_page`.
     pub fn get_districts_by_region(
         &self,
         region_id: u64,
         from_index: Option<u64>,
         limit: Option<u64>,
+        lang: Option<String>,
     ) -> Vec<(u64, District)> {
         let keys = self.districts.keys_as_vector();
         let values = self.districts.values_as_vector();
         let from_index = from_index.unwrap_or(0);
         let limit = limit.unwrap_or(keys.len());
-        (from_index..std::cmp::min(keys.len(), limit))
+        let districts = (from_index..std::cmp::min(keys.len(), limit))
             .filter(|index| values.get(*index).unwrap().region_id == region_id)
-            .map(|index| (keys.get(index).unwrap(), values.get(index).unwrap().into()))
-            .collect()
+            .map(|index| (keys.get(index).unwrap(), values.get(index).unwrap()))
+            .collect();
+        self.internal_localize_all(EntityKind::District, districts, &lang)
     }
 
     pub fn add_candidates(&mut self, candidates: Vec<(u64, Candidate)>) {
-        self.assert_access();
-        for data in candidates {
-            self.candidates.insert(&data.0, &data.1);
-        }
+        self.assert_role(Role::DataEditor);
+        self.assert_valid_candidates(&candidates, 0);
+        let changes = candidates
+            .into_iter()
+            .map(|(id, candidate)| self.internal_put_candidate(id, candidate))
+            .collect();
+        emit_changes(changes);
     }
 
     pub fn get_candidates(
         &self,
         from_index: Option<u64>,
         limit: Option<u64>,
+        lang: Option<String>,
     ) -> Vec<(u64, Candidate)> {
-        unordered_map_pagination(&self.candidates, from_index, limit)
+        let candidates = unordered_map_pagination(&self.candidates, from_index, limit);
+        self.internal_localize_all(EntityKind::Candidate, candidates, &lang)
     }
 
-    // recommendations: [campaign_id: u64, district_id: u64, candidate_id: u64]
-    pub fn add_recommendations(&mut self, recommendations: Vec<(u64, u64, u64)>) {
-        self.assert_access();
-
-        for data in recommendations {
-            let campaign_id = data.0;
-            let district_id = data.1;
-            let candidate_id = data.2;
-
-            self.recommendations.insert(
-                &RecommendationIndex {
+    /// Units without their own recommendation get the one of the nearest parent unit.
+    pub fn get_votesmart(
+        &self,
+        campaign_id: u64,
+        district_id: u64,
+        lang: Option<String>,
+    ) -> Option<Recommendation> {
+        let campaign = self.campaigns.get(&campaign_id)?;
+        let district = self.districts.get(&district_id)?;
+        let record = std::iter::once(district_id)
+            .chain(
+                self.internal_district_ancestors(&district)
+                    .into_iter()
+                    .map(|(id, _)| id),
+            )
+            .find_map(|district_id| {
+                self.internal_published_recommendation(&RecommendationIndex {
                     campaign_id,
                     district_id,
-                },
-                &candidate_id,
-            );
+                })
+            })?;
+        let (candidates, fallback_applied) = self.internal_resolve_candidates(&record);
+        let candidates: Vec<RecommendedCandidate> = candidates
+            .into_iter()
+            .map(|(candidate_id, candidate)| {
+                let party_id = candidate.party_id;
+                RecommendedCandidate {
+                    candidate_id,
+                    title: self.internal_localize(
+                        EntityKind::Candidate,
+                        candidate_id,
+                        candidate.title,
+                        &lang,
+                    ),
+                    party_id,
+                    party: self.parties.get(&party_id).map(|party| {
+                        self.internal_localize(EntityKind::Party, party_id, party, &lang)
+                    }),
+                }
+            })
+            .collect();
+        let first = candidates.first()?.clone();
+
+        Some(Recommendation {
+            candidate_id: first.candidate_id,
+            title: first.title,
+            party_id: first.party_id,
+            party: first.party,
+            candidates,
+            seats: record.seats,
+            fallback_applied,
+            campaign_title: self.internal_localize(
+                EntityKind::Campaign,
+                campaign_id,
+                campaign.title,
+                &lang,
+            ),
+            district_title: self.internal_localize(
+                EntityKind::District,
+                district_id,
+                district.title,
+                &lang,
+            ),
+            rationale: record.rationale,
+            updated_at: record.updated_at,
+        })
+    }
+}
+
+impl VoteSmart {
+    /// Empty state of the current layout.
+    pub(crate) fn internal_new(master_account_id: AccountId) -> Self {
+        write_state_version(STATE_VERSION);
+        Self {
+            master_account_id,
+            parties: UnorderedMap::new(StorageKey::Parties),
+            campaigns: UnorderedMap::new(StorageKey::CampaignsV2),
+            regions: UnorderedMap::new(StorageKey::Regions),
+            districts: UnorderedMap::new(StorageKey::DistrictsV2),
+            candidates: UnorderedMap::new(StorageKey::CandidatesV2),
+            recommendations: LookupMap::new(StorageKey::RecommendationsV2),
+
+            roles: UnorderedMap::new(StorageKey::Roles),
+            pending_admin: None,
+            admin_transfer_delay: DEFAULT_ADMIN_TRANSFER_DELAY,
+            pending_admin_transfer_delay: None,
+            recommendation_proposals: UnorderedMap::new(StorageKey::RecommendationProposals),
+            next_proposal_id: 0,
+            approval_policy: ApprovalPolicy {
+                approvals_required: 1,
+                proposal_ttl: DEFAULT_PROPOSAL_TTL.into(),
+            },
+            candidate_recommendations: LookupMap::new(StorageKey::CandidateRecommendations),
+            recommended_districts: LookupMap::new(StorageKey::RecommendedDistricts),
+            recommendation_history: LookupMap::new(StorageKey::RecommendationHistory),
+            batch_publish_at: LookupMap::new(StorageKey::BatchPublishAt),
+            withheld_batches: LookupMap::new(StorageKey::WithheldBatches),
+            merkle_leaf_positions: LookupMap::new(StorageKey::MerkleLeafPositions),
+            merkle_trees: LookupMap::new(StorageKey::MerkleTrees),
+            merkle_nodes: LookupMap::new(StorageKey::MerkleNodes),
+            unit_children: LookupMap::new(StorageKey::UnitChildren),
+            region_districts: LookupMap::new(StorageKey::RegionDistricts),
+            party_candidates: LookupMap::new(StorageKey::PartyCandidates),
+            candidate_profiles: LookupMap::new(StorageKey::CandidateProfiles),
+            translations: LookupMap::new(StorageKey::Translations),
+            default_language: DEFAULT_LANGUAGE.to_string(),
+            translated_entities: 0,
+            migration: None,
+            collection_storage: HashMap::new(),
+            storage_meter: StorageMeter::default(),
         }
     }
+}
 
-    pub fn get_votesmart(&self, campaign_id: u64, district_id: u64) -> Option<Recommendation> {
-        let candidate_id = self.recommendations.get(&RecommendationIndex {
-            campaign_id,
-            district_id,
-        });
-
-        if let Some(candidate_id_unwrapped) = candidate_id {
-            if let Some(candidate_unwrapped) = self.candidates.get(&candidate_id_unwrapped) {
-                let result = Recommendation {
-                    title: candidate_unwrapped.title,
-                    party: self
-                        .parties
-                        .get(&candidate_unwrapped.party_id)
-                        .unwrap_or("Unknown".to_string()),
-                };
-                Some(result)
-            } else {
-                None
-            }
-        } else {
-            None
+impl VoteSmart {
+    /// Stores the row as is, the callers validate it. Districts and candidates
+    /// referenced by a frozen campaign are refused.
+    pub(crate) fn internal_put_party(&mut self, id: u64, party: String) -> EntityChange {
+        self.assert_entity_not_frozen(EntityKind::Party, id);
+        let old = self.parties.insert(&id, &party);
+        self.internal_charge_storage(StorageCollection::Parties);
+        EntityChange::new("party", id, old.as_ref(), Some(&party))
+    }
+
+    pub(crate) fn internal_put_region(&mut self, id: u64, region: Region) -> EntityChange {
+        let old = self.regions.insert(&id, &region);
+        self.internal_charge_storage(StorageCollection::Regions);
+        EntityChange::new("region", id, old.as_ref(), Some(&region))
+    }
+
+    pub(crate) fn internal_put_district(&mut self, id: u64, district: District) -> EntityChange {
+        self.assert_district_not_frozen(id, &district);
+        let old = self.internal_insert_district(id, &district);
+        self.internal_charge_storage(StorageCollection::Districts);
+        EntityChange::new("district", id, old.as_ref(), Some(&district))
+    }
+
+    pub(crate) fn internal_put_candidate(&mut self, id: u64, candidate: Candidate) -> EntityChange {
+        self.assert_candidate_not_frozen(id);
+        if let Some(old) = self.candidates.get(&id) {
+            // the status decides who of the listed candidates is recommended
+            assert!(
+                old.status == candidate.status
+                    || self.internal_candidate_recommendations(id).is_empty()
+                    || self.has_role(&env::predecessor_account_id(), Role::RecommendationCurator),
+                "Status of a recommended candidate is changed by a recommendation curator"
+            );
         }
+        let old = self.internal_insert_candidate(id, &candidate);
+        self.internal_charge_storage(StorageCollection::Candidates);
+        EntityChange::new("candidate", id, old.as_ref(), Some(&candidate))
     }
 }
 
+/// Kept for the methods released before `Page`: `limit` is the end index, not the page size.
 pub(crate) fn unordered_map_pagination<K, VV, V>(
     m: &UnorderedMap<K, VV>,
     from_index: Option<u64>,
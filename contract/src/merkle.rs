@@ -0,0 +1,338 @@
+use crate::*;
+use near_sdk::json_types::Base64VecU8;
+
+pub type MerkleHash = [u8; 32];
+
+const LEAF_PREFIX: u8 = 0;
+const NODE_PREFIX: u8 = 1;
+
+/// Hash of a missing leaf. Missing nodes above it hash pairs of missing children.
+const EMPTY_LEAF: MerkleHash = [0; 32];
+
+#[derive(BorshDeserialize, BorshSerialize, Default)]
+pub struct MerkleTree {
+    /// Leaves ever allocated, removed recommendations keep their empty leaf.
+    pub leaves_count: u64,
+    pub root: MerkleHash,
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct MerkleNodeIndex {
+    pub campaign_id: u64,
+    /// 0 for leaves.
+    pub level: u8,
+    pub index: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct MerkleProof {
+    pub leaf_index: U64,
+    pub leaves_count: U64,
+    pub leaf: Base64VecU8,
+    /// Sibling hashes from the leaf level up to the root.
+    pub siblings: Vec<Base64VecU8>,
+    pub root: Base64VecU8,
+}
+
+#[near_bindgen]
+impl VoteSmart {
+    /// Root over all recommendations of the campaign, including not yet published ones.
+    pub fn get_merkle_root(&self, campaign_id: u64) -> Base64VecU8 {
+        self.merkle_trees
+            .get(&campaign_id)
+            .unwrap_or_default()
+            .root
+            .to_vec()
+            .into()
+    }
+
+    /// Inclusion proof of the published recommendation for the district.
+    /// Nothing is returned while the current recommendation is embargoed.
+    pub fn get_recommendation_proof(
+        &self,
+        campaign_id: u64,
+        district_id: u64,
+    ) -> Option<MerkleProof> {
+        let index = RecommendationIndex {
+            campaign_id,
+            district_id,
+        };
+        let record = self.recommendations.get(&index)?;
+        if !self.internal_is_published(campaign_id, None)
+            || !self.internal_is_published(campaign_id, record.proposal_id)
+        {
+            return None;
+        }
+        let position = self.merkle_leaf_positions.get(&index)?;
+        let tree = self.merkle_trees.get(&campaign_id)?;
+
+        let mut siblings = vec![];
+        let mut empty = EMPTY_LEAF;
+        for level in 0..merkle_depth(tree.leaves_count) {
+            let sibling = self
+                .merkle_nodes
+                .get(&MerkleNodeIndex {
+                    campaign_id,
+                    level,
+                    index: (position >> level) ^ 1,
+                })
+                .unwrap_or(empty);
+            siblings.push(sibling.to_vec().into());
+            empty = merkle_node(&empty, &empty);
+        }
+        Some(MerkleProof {
+            leaf_index: position.into(),
+            leaves_count: tree.leaves_count.into(),
+            leaf: merkle_leaf(district_id, &record).to_vec().into(),
+            siblings,
+            root: tree.root.to_vec().into(),
+        })
+    }
+}
+
+impl VoteSmart {
+    /// Writes the leaf of the recommendation and recomputes its path to the root.
+    /// `None` empties the leaf of a removed recommendation.
+    pub(crate) fn internal_update_merkle_leaf(
+        &mut self,
+        index: &RecommendationIndex,
+        record: Option<&RecommendationRecord>,
+    ) {
+        let campaign_id = index.campaign_id;
+        let mut tree = self.merkle_trees.get(&campaign_id).unwrap_or_default();
+        let position = match self.merkle_leaf_positions.get(index) {
+            Some(position) => position,
+            None if record.is_none() => return,
+            None => {
+                let position = tree.leaves_count;
+                tree.leaves_count += 1;
+                self.merkle_leaf_positions.insert(index, &position);
+                position
+            }
+        };
+
+        let mut hash = record
+            .map(|record| merkle_leaf(index.district_id, record))
+            .unwrap_or(EMPTY_LEAF);
+        let mut empty = EMPTY_LEAF;
+        let mut node_index = position;
+        for level in 0..merkle_depth(tree.leaves_count) {
+            self.merkle_nodes.insert(
+                &MerkleNodeIndex {
+                    campaign_id,
+                    level,
+                    index: node_index,
+                },
+                &hash,
+            );
+            let sibling = self
+                .merkle_nodes
+                .get(&MerkleNodeIndex {
+                    campaign_id,
+                    level,
+                    index: node_index ^ 1,
+                })
+                .unwrap_or(empty);
+            hash = if node_index.is_multiple_of(2) {
+                merkle_node(&hash, &sibling)
+            } else {
+                merkle_node(&sibling, &hash)
+            };
+            empty = merkle_node(&empty, &empty);
+            node_index /= 2;
+        }
+        // the root is kept as a node too, it becomes a child once the tree grows
+        self.merkle_nodes.insert(
+            &MerkleNodeIndex {
+                campaign_id,
+                level: merkle_depth(tree.leaves_count),
+                index: 0,
+            },
+            &hash,
+        );
+        tree.root = hash;
+        self.merkle_trees.insert(&campaign_id, &tree);
+    }
+}
+
+/// Number of levels above the leaves.
+fn merkle_depth(leaves_count: u64) -> u8 {
+    let mut depth = 0;
+    while (1u64 << depth) < leaves_count {
+        depth += 1;
+    }
+    depth
+}
+
+/// sha256(0x00 || district_id || seats || candidate ids), as little-endian u64, u32 and u64s.
+fn merkle_leaf(district_id: u64, record: &RecommendationRecord) -> MerkleHash {
+    let mut data = vec![LEAF_PREFIX];
+    data.extend_from_slice(&district_id.to_le_bytes());
+    data.extend_from_slice(&record.seats.to_le_bytes());
+    for candidate_id in record.candidate_ids.iter() {
+        data.extend_from_slice(&candidate_id.to_le_bytes());
+    }
+    sha256(&data)
+}
+
+/// sha256(0x01 || left || right)
+fn merkle_node(left: &MerkleHash, right: &MerkleHash) -> MerkleHash {
+    let mut data = vec![NODE_PREFIX];
+    data.extend_from_slice(left);
+    data.extend_from_slice(right);
+    sha256(&data)
+}
+
+fn sha256(data: &[u8]) -> MerkleHash {
+    let mut hash = [0; 32];
+    hash.copy_from_slice(&env::sha256(data));
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_sdk::test_utils::{accounts, VMContextBuilder};
+    use near_sdk::{testing_env, MockedBlockchain};
+
+    fn setup() -> VoteSmart {
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(accounts(0))
+            .build());
+        let mut state = VoteSmart::new(None);
+        state.add_campaign(1, "Duma".to_string(), 0.into(), CampaignLevel::Federal);
+        state.set_campaign_status(1, CampaignStatus::Published);
+        state.add_parties(vec![(1, "Party".to_string())]);
+        state.add_regions(vec![(
+            16,
+            Region {
+                title: "Tatarstan".to_string(),
+            },
+        )]);
+        state.add_districts(
+            (1..=5)
+                .map(|id| {
+                    (
+                        id,
+                        District {
+                            region_id: 16,
+                            title: format!("District {}", id),
+                            unit_type: UnitType::District,
+                            parent_id: None,
+                            address: None,
+                        },
+                    )
+                })
+                .collect(),
+        );
+        state.add_candidates(
+            [10, 11]
+                .iter()
+                .map(|id| {
+                    (
+                        *id,
+                        Candidate {
+                            title: format!("Candidate {}", id),
+                            party_id: 1,
+                            status: CandidateStatus::Active,
+                        },
+                    )
+                })
+                .collect(),
+        );
+        state
+    }
+
+    /// The leaf as documented in the README, built without `merkle_leaf`.
+    fn documented_leaf(district_id: u64, candidate_id: u64) -> MerkleHash {
+        let mut data = vec![0u8];
+        data.extend_from_slice(&district_id.to_le_bytes());
+        data.extend_from_slice(&1u32.to_le_bytes());
+        data.extend_from_slice(&candidate_id.to_le_bytes());
+        sha256(&data)
+    }
+
+    fn documented_node(left: &[u8], right: &[u8]) -> MerkleHash {
+        let mut data = vec![1u8];
+        data.extend_from_slice(left);
+        data.extend_from_slice(right);
+        sha256(&data)
+    }
+
+    /// Root of the whole tree, missing leaves up to the next power of two are zeros.
+    fn full_root(leaves: &[MerkleHash]) -> MerkleHash {
+        let mut level = leaves.to_vec();
+        level.resize(leaves.len().next_power_of_two(), EMPTY_LEAF);
+        while level.len() > 1 {
+            level = level
+                .chunks(2)
+                .map(|pair| documented_node(&pair[0], &pair[1]))
+                .collect();
+        }
+        level[0]
+    }
+
+    fn root_from_proof(proof: &MerkleProof) -> Vec<u8> {
+        let mut hash = proof.leaf.0.clone();
+        let mut index = proof.leaf_index.0;
+        for sibling in proof.siblings.iter() {
+            hash = if index.is_multiple_of(2) {
+                documented_node(&hash, &sibling.0)
+            } else {
+                documented_node(&sibling.0, &hash)
+            }
+            .to_vec();
+            index /= 2;
+        }
+        hash
+    }
+
+    /// `leaves` holds (district_id, candidate_id) by leaf position, `None` for removed ones.
+    fn assert_proofs(state: &VoteSmart, leaves: &[(u64, Option<u64>)]) {
+        let hashes: Vec<MerkleHash> = leaves
+            .iter()
+            .map(|(district_id, candidate_id)| {
+                candidate_id.map_or(EMPTY_LEAF, |candidate_id| {
+                    documented_leaf(*district_id, candidate_id)
+                })
+            })
+            .collect();
+        let root = full_root(&hashes);
+        assert_eq!(state.get_merkle_root(1).0, root.to_vec());
+        for (position, (district_id, candidate_id)) in leaves.iter().enumerate() {
+            let proof = state.get_recommendation_proof(1, *district_id);
+            if candidate_id.is_none() {
+                assert!(proof.is_none());
+                continue;
+            }
+            let proof = proof.unwrap();
+            assert_eq!(proof.leaf_index.0, position as u64);
+            assert_eq!(proof.leaves_count.0, leaves.len() as u64);
+            assert_eq!(proof.leaf.0, hashes[position].to_vec());
+            assert_eq!(proof.root.0, root.to_vec());
+            assert_eq!(root_from_proof(&proof), root.to_vec());
+        }
+    }
+
+    #[test]
+    fn proofs_rebuild_the_root() {
+        let mut state = setup();
+        let mut leaves = vec![];
+        for (district_id, candidate_id) in [(1, 10), (2, 11), (3, 10), (4, 11), (5, 10)].iter() {
+            state.add_recommendations(vec![(1, *district_id, *candidate_id)], None);
+            leaves.push((*district_id, Some(*candidate_id)));
+            if [1, 2, 3, 5].contains(&leaves.len()) {
+                assert_proofs(&state, &leaves);
+            }
+        }
+
+        state.add_recommendations(vec![(1, 2, 10)], None);
+        leaves[1] = (2, Some(10));
+        assert_proofs(&state, &leaves);
+
+        state.remove_recommendations(vec![(1, 3)], None);
+        leaves[2] = (3, None);
+        assert_proofs(&state, &leaves);
+    }
+}
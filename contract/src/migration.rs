@@ -0,0 +1,468 @@
+use crate::*;
+use near_sdk::json_types::Base64VecU8;
+use near_sdk::{Gas, IntoStorageKey, Promise};
+
+/// Layout of the contract state. Kept under its own storage key, so that it
+/// is known before the state is deserialized.
+pub(crate) const STATE_VERSION: u32 = 2;
+
+/// Steps done by `migrate_continue` when `limit` is not given.
+const DEFAULT_MIGRATION_LIMIT: u64 = 100;
+
+/// Gas for the `migrate` call that follows the deployment in `upgrade`.
+const GAS_FOR_MIGRATE: Gas = 30_000_000_000_000;
+
+/// State of the first release, stored without a version.
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct VoteSmartV1 {
+    pub master_account_id: AccountId,
+    pub parties: UnorderedMap<u64, String>,
+    pub campaigns: UnorderedMap<u64, String>,
+    pub regions: UnorderedMap<u64, Region>,
+    pub districts: UnorderedMap<u64, DistrictV1>,
+    pub candidates: UnorderedMap<u64, CandidateV1>,
+    pub recommendations: LookupMap<RecommendationIndex, u64>,
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct DistrictV1 {
+    pub region_id: u64,
+    pub title: String,
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct CandidateV1 {
+    pub title: String,
+    pub party_id: u64,
+}
+
+/// Collections of the previous layout whose rows are not moved yet.
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct Migration {
+    campaigns: UnorderedMap<u64, String>,
+    districts: UnorderedMap<u64, DistrictV1>,
+    candidates: UnorderedMap<u64, CandidateV1>,
+    recommendations: LookupMap<RecommendationIndex, u64>,
+    /// Next (campaign, district) pair checked for a recommendation,
+    /// counted over the campaigns and districts of the new layout.
+    position: u64,
+    /// Recommendations moved so far.
+    recommendations_moved: u64,
+}
+
+#[near_bindgen]
+impl VoteSmart {
+    /// Converts the stored state to the current layout. Called by the admin or by the
+    /// contract itself after a new version is deployed. Only the collections are taken
+    /// over here, their rows are moved by `migrate_continue`, and write methods are
+    /// refused until it is done. A state of the current layout is kept as is.
+    #[init(ignore_state)]
+    pub fn migrate() -> Self {
+        match read_state_version() {
+            STATE_VERSION => {
+                let state: Self = env::state_read().expect("No state to migrate");
+                assert_migrate_access(&state.master_account_id);
+                state
+            }
+            1 => {
+                let old: VoteSmartV1 = env::state_read().expect("No state to migrate");
+                assert_migrate_access(&old.master_account_id);
+                let mut state = Self::internal_new(old.master_account_id);
+                state.parties = old.parties;
+                state.regions = old.regions;
+                state.migration = Some(Migration {
+                    campaigns: old.campaigns,
+                    districts: old.districts,
+                    candidates: old.candidates,
+                    recommendations: old.recommendations,
+                    position: 0,
+                    recommendations_moved: 0,
+                });
+                state
+            }
+            _ => env::panic(b"State is newer than the contract"),
+        }
+    }
+
+    /// Deploys new code to the contract account and migrates the state in the same batch,
+    /// so the deployment is reverted if the new code can't read the state. Allowed while
+    /// a migration is in progress, to fix it.
+    pub fn upgrade(&mut self, code: Base64VecU8) -> Promise {
+        assert_eq!(
+            env::predecessor_account_id(),
+            self.master_account_id,
+            "No access"
+        );
+        Promise::new(env::current_account_id())
+            .deploy_contract(code.into())
+            .function_call(b"migrate".to_vec(), b"{}".to_vec(), 0, GAS_FOR_MIGRATE)
+    }
+
+    /// Moves up to `limit` rows of the previous layout. Returns true when the migration is done.
+    /// Campaigns of the first release become published federal campaigns without
+    /// an election date, use `update_campaign` to fill them in.
+    pub fn migrate_continue(&mut self, limit: Option<u64>) -> bool {
+        assert_eq!(
+            env::predecessor_account_id(),
+            self.master_account_id,
+            "No access"
+        );
+        let mut migration = self.migration.take().expect("No migration in progress");
+        for _ in 0..limit.unwrap_or(DEFAULT_MIGRATION_LIMIT) {
+            if !self.internal_migrate_row(&mut migration) {
+                env::log(
+                    format!(
+                        "Migration is done, {} recommendations moved. Recommendations of campaigns \
+                         or districts that are not stored can't be found, see migrate_recommendations",
+                        migration.recommendations_moved
+                    )
+                    .as_bytes(),
+                );
+                return true;
+            }
+        }
+        self.migration = Some(migration);
+        false
+    }
+
+    /// Moves the recommendations of the given (campaign_id, district_id) pairs from the
+    /// first release layout. `migrate_continue` only finds the pairs of stored campaigns and
+    /// districts, the first release kept recommendations of removed ones too. They show up
+    /// once the campaign and the district are added again. Returns the number of moved rows.
+    pub fn migrate_recommendations(&mut self, recommendations: Vec<(u64, u64)>) -> u64 {
+        assert_eq!(
+            env::predecessor_account_id(),
+            self.master_account_id,
+            "No access"
+        );
+        let mut old: LookupMap<RecommendationIndex, u64> =
+            LookupMap::new(StorageKey::Recommendations);
+        let total = recommendations.len();
+        let mut moved = 0;
+        for (campaign_id, district_id) in recommendations {
+            let index = RecommendationIndex {
+                campaign_id,
+                district_id,
+            };
+            if let Some(candidate_id) = old.remove(&index) {
+                self.internal_migrate_recommendation(&index, candidate_id);
+                moved += 1;
+            }
+        }
+        env::log(format!("Moved {} of {} recommendations", moved, total).as_bytes());
+        moved
+    }
+
+    pub fn get_state_version(&self) -> u32 {
+        read_state_version()
+    }
+
+    pub fn is_migrating(&self) -> bool {
+        self.migration.is_some()
+    }
+}
+
+impl VoteSmart {
+    pub(crate) fn assert_not_migrating(&self) {
+        assert!(self.migration.is_none(), "State migration is in progress");
+    }
+
+    /// Moves one row, or checks one (campaign, district) pair for a recommendation.
+    /// Returns false if nothing is left.
+    fn internal_migrate_row(&mut self, migration: &mut Migration) -> bool {
+        if let Some(id) = last_key(&migration.campaigns) {
+            let title = migration.campaigns.remove(&id).unwrap();
+            self.internal_skip_storage();
+            self.campaigns.insert(
+                &id,
+                &Campaign {
+                    title,
+                    election_date: 0.into(),
+                    level: CampaignLevel::Federal,
+                    status: CampaignStatus::Published,
+                    publish_at: None,
+                    frozen_at: None,
+                },
+            );
+            self.internal_charge_storage(StorageCollection::Campaigns);
+            return true;
+        }
+        if let Some(id) = last_key(&migration.districts) {
+            let district = migration.districts.remove(&id).unwrap();
+            self.internal_skip_storage();
+            self.internal_insert_district(
+                id,
+                &District {
+                    region_id: district.region_id,
+                    title: district.title,
+                    unit_type: UnitType::District,
+                    parent_id: None,
+                    address: None,
+                },
+            );
+            self.internal_charge_storage(StorageCollection::Districts);
+            return true;
+        }
+        if let Some(id) = last_key(&migration.candidates) {
+            let candidate = migration.candidates.remove(&id).unwrap();
+            self.internal_skip_storage();
+            self.internal_insert_candidate(
+                id,
+                &Candidate {
+                    title: candidate.title,
+                    party_id: candidate.party_id,
+                    status: CandidateStatus::Active,
+                },
+            );
+            self.internal_charge_storage(StorageCollection::Candidates);
+            return true;
+        }
+
+        // the old recommendations can't be listed, so every pair is checked
+        let campaign_ids = self.campaigns.keys_as_vector();
+        let district_ids = self.districts.keys_as_vector();
+        if migration.position >= campaign_ids.len() * district_ids.len() {
+            return false;
+        }
+        let index = RecommendationIndex {
+            campaign_id: campaign_ids
+                .get(migration.position / district_ids.len())
+                .unwrap(),
+            district_id: district_ids
+                .get(migration.position % district_ids.len())
+                .unwrap(),
+        };
+        migration.position += 1;
+        if let Some(candidate_id) = migration.recommendations.remove(&index) {
+            self.internal_migrate_recommendation(&index, candidate_id);
+            migration.recommendations_moved += 1;
+        }
+        true
+    }
+
+    /// Stores a recommendation removed from the first release layout.
+    fn internal_migrate_recommendation(&mut self, index: &RecommendationIndex, candidate_id: u64) {
+        self.internal_skip_storage();
+        self.internal_set_recommendation(
+            index,
+            &RecommendationRecord {
+                candidate_ids: vec![candidate_id],
+                seats: 1,
+                rationale: None,
+                updated_at: env::block_timestamp().into(),
+                proposal_id: None,
+            },
+            &ChangeOrigin::predecessor(),
+        );
+    }
+}
+
+fn assert_migrate_access(master_account_id: &AccountId) {
+    let caller = env::predecessor_account_id();
+    assert!(
+        caller == *master_account_id || caller == env::current_account_id(),
+        "No access"
+    );
+}
+
+/// Version 1 is the state stored before versioning.
+pub(crate) fn read_state_version() -> u32 {
+    env::storage_read(&StorageKey::StateVersion.into_storage_key())
+        .map(|value| u32::try_from_slice(&value).expect("Invalid state version"))
+        .unwrap_or(1)
+}
+
+pub(crate) fn write_state_version(version: u32) {
+    env::storage_write(
+        &StorageKey::StateVersion.into_storage_key(),
+        &version.try_to_vec().unwrap(),
+    );
+}
+
+fn last_key<V>(m: &UnorderedMap<u64, V>) -> Option<u64>
+where
+    V: BorshSerialize + BorshDeserialize,
+{
+    let keys = m.keys_as_vector();
+    if keys.is_empty() {
+        None
+    } else {
+        keys.get(keys.len() - 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_sdk::test_utils::{accounts, VMContextBuilder};
+    use near_sdk::{testing_env, MockedBlockchain};
+
+    fn set_caller(account_id: ValidAccountId) {
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(accounts(5))
+            .predecessor_account_id(account_id)
+            .build());
+    }
+
+    /// Writes a state of the first release: two campaigns, two districts, two recommendations.
+    fn write_v1_state() {
+        set_caller(accounts(0));
+        let mut state = VoteSmartV1 {
+            master_account_id: accounts(0).into(),
+            parties: UnorderedMap::new(StorageKey::Parties),
+            campaigns: UnorderedMap::new(StorageKey::Campaigns),
+            regions: UnorderedMap::new(StorageKey::Regions),
+            districts: UnorderedMap::new(StorageKey::Districts),
+            candidates: UnorderedMap::new(StorageKey::Candidates),
+            recommendations: LookupMap::new(StorageKey::Recommendations),
+        };
+        state.parties.insert(&1, &"Party".to_string());
+        state.campaigns.insert(&1, &"Duma".to_string());
+        state.campaigns.insert(&2, &"City".to_string());
+        state.regions.insert(
+            &16,
+            &Region {
+                title: "Tatarstan".to_string(),
+            },
+        );
+        for id in [100, 101].iter() {
+            state.districts.insert(
+                id,
+                &DistrictV1 {
+                    region_id: 16,
+                    title: format!("District {}", id),
+                },
+            );
+        }
+        state.candidates.insert(
+            &7,
+            &CandidateV1 {
+                title: "Candidate".to_string(),
+                party_id: 1,
+            },
+        );
+        for (campaign_id, district_id) in [(1, 100), (2, 101)].iter() {
+            state.recommendations.insert(
+                &RecommendationIndex {
+                    campaign_id: *campaign_id,
+                    district_id: *district_id,
+                },
+                &7,
+            );
+        }
+        env::state_write(&state);
+    }
+
+    fn migrate_all(state: &mut VoteSmart) {
+        let mut calls = 0;
+        while !state.migrate_continue(Some(3)) {
+            calls += 1;
+            assert!(calls < 100);
+        }
+    }
+
+    #[test]
+    fn migrates_v1_state() {
+        write_v1_state();
+        let mut state = VoteSmart::migrate();
+        assert_eq!(state.get_state_version(), STATE_VERSION);
+        assert!(state.is_migrating());
+        migrate_all(&mut state);
+        assert!(!state.is_migrating());
+
+        assert_eq!(state.get_parties(None, None, None).len(), 1);
+        assert_eq!(state.get_regions(None, None, None).len(), 1);
+        let campaign = state.get_campaign(2, None).unwrap();
+        assert_eq!(campaign.title, "City");
+        assert!(campaign.status == CampaignStatus::Published);
+        assert_eq!(state.get_districts_by_region(16, None, None, None).len(), 2);
+        assert_eq!(
+            state.get_candidate(7, None).unwrap().candidate.title,
+            "Candidate"
+        );
+
+        let recommendation = state.get_votesmart(1, 100, None).unwrap();
+        assert_eq!(recommendation.candidate_id, 7);
+        assert_eq!(recommendation.party, Some("Party".to_string()));
+        assert!(state.get_votesmart(2, 101, None).is_some());
+        assert!(state.get_votesmart(1, 101, None).is_none());
+        assert_eq!(state.get_candidate_recommendations(7).len(), 2);
+        assert!(state.get_recommendation_proof(1, 100).is_some());
+    }
+
+    #[test]
+    fn migrates_known_orphaned_pairs() {
+        write_v1_state();
+        let mut old: LookupMap<RecommendationIndex, u64> =
+            LookupMap::new(StorageKey::Recommendations);
+        let orphan = RecommendationIndex {
+            campaign_id: 3,
+            district_id: 100,
+        };
+        old.insert(&orphan, &7);
+        let mut state = VoteSmart::migrate();
+        migrate_all(&mut state);
+        assert!(state.recommendations.get(&orphan).is_none());
+
+        assert_eq!(
+            state.migrate_recommendations(vec![(3, 100), (1, 100), (9, 9)]),
+            1
+        );
+        assert_eq!(
+            state.recommendations.get(&orphan).unwrap().candidate_ids,
+            vec![7]
+        );
+        assert_eq!(state.migrate_recommendations(vec![(3, 100)]), 0);
+    }
+
+    #[test]
+    fn migrate_by_contract_account() {
+        write_v1_state();
+        set_caller(accounts(5));
+        VoteSmart::migrate();
+    }
+
+    #[test]
+    #[should_panic(expected = "No access")]
+    fn migrate_by_other_account() {
+        write_v1_state();
+        set_caller(accounts(1));
+        VoteSmart::migrate();
+    }
+
+    #[test]
+    fn migrate_twice() {
+        write_v1_state();
+        let mut state = VoteSmart::migrate();
+        migrate_all(&mut state);
+        env::state_write(&state);
+        let state = VoteSmart::migrate();
+        assert!(!state.is_migrating());
+        assert!(state.get_votesmart(1, 100, None).is_some());
+    }
+
+    #[test]
+    #[should_panic(expected = "State is newer than the contract")]
+    fn migrate_newer_state() {
+        write_v1_state();
+        write_state_version(STATE_VERSION + 1);
+        VoteSmart::migrate();
+    }
+
+    #[test]
+    #[should_panic(expected = "No access")]
+    fn upgrade_by_other_account() {
+        set_caller(accounts(0));
+        let mut state = VoteSmart::new(None);
+        set_caller(accounts(1));
+        state.upgrade(vec![0].into());
+    }
+
+    #[test]
+    #[should_panic(expected = "State migration is in progress")]
+    fn writes_wait_for_migration() {
+        write_v1_state();
+        let mut state = VoteSmart::migrate();
+        state.add_parties(vec![(2, "Other".to_string())]);
+    }
+}
@@ -0,0 +1,219 @@
+use crate::*;
+
+/// Page size used when `limit` is not given.
+pub(crate) const DEFAULT_PAGE_LIMIT: u64 = 100;
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    /// Number of items in the whole list.
+    pub total: u64,
+    /// Pass as `cursor` to get the next page, `None` on the last page.
+    /// It is a position, not a key: a removal moves the last item to the freed position,
+    /// so an item may be missed if `total` dropped since the first page. Start over then.
+    pub next_cursor: Option<u64>,
+}
+
+impl<T> Page<T> {
+    /// Page over a list already loaded in memory, e.g. a filtered one.
+    pub(crate) fn from_vec(items: Vec<T>, cursor: Option<u64>, limit: Option<u64>) -> Self {
+        let total = items.len() as u64;
+        let (start, end) = page_bounds(total, cursor, limit);
+        Page {
+            items: items
+                .into_iter()
+                .skip(start as usize)
+                .take((end - start) as usize)
+                .collect(),
+            total,
+            next_cursor: next_cursor(total, end),
+        }
+    }
+}
+
+/// The `*_page` methods return a `Page`, unlike the methods of the same name without
+/// the suffix, where `limit` is the end index rather than the page size.
+#[near_bindgen]
+impl VoteSmart {
+    pub fn get_campaigns_page(
+        &self,
+        cursor: Option<u64>,
+        limit: Option<u64>,
+        status: Option<CampaignStatus>,
+        lang: Option<String>,
+    ) -> Page<(u64, Campaign)> {
+        let mut page = match status {
+            Some(status) => Page::from_vec(
+                self.campaigns
+                    .iter()
+                    .filter(|(_, campaign)| campaign.status == status)
+                    .collect(),
+                cursor,
+                limit,
+            ),
+            None => unordered_map_page(&self.campaigns, cursor, limit),
+        };
+        page.items = self.internal_localize_all(EntityKind::Campaign, page.items, &lang);
+        page
+    }
+
+    pub fn get_parties_page(
+        &self,
+        cursor: Option<u64>,
+        limit: Option<u64>,
+        lang: Option<String>,
+    ) -> Page<(u64, String)> {
+        let mut page = unordered_map_page(&self.parties, cursor, limit);
+        page.items = self.internal_localize_all(EntityKind::Party, page.items, &lang);
+        page
+    }
+
+    pub fn get_regions_page(
+        &self,
+        cursor: Option<u64>,
+        limit: Option<u64>,
+        lang: Option<String>,
+    ) -> Page<(u64, Region)> {
+        let mut page = unordered_map_page(&self.regions, cursor, limit);
+        page.items = self.internal_localize_all(EntityKind::Region, page.items, &lang);
+        page
+    }
+
+    pub fn get_districts_page(
+        &self,
+        cursor: Option<u64>,
+        limit: Option<u64>,
+        lang: Option<String>,
+    ) -> Page<(u64, District)> {
+        let mut page = unordered_map_page(&self.districts, cursor, limit);
+        page.items = self.internal_localize_all(EntityKind::District, page.items, &lang);
+        page
+    }
+
+    pub fn get_districts_by_region_page(
+        &self,
+        region_id: u64,
+        cursor: Option<u64>,
+        limit: Option<u64>,
+        lang: Option<String>,
+    ) -> Page<(u64, District)> {
+        self.internal_districts_page(self.region_districts.get(&region_id), cursor, limit, &lang)
+    }
+
+    pub fn get_candidates_page(
+        &self,
+        cursor: Option<u64>,
+        limit: Option<u64>,
+        lang: Option<String>,
+    ) -> Page<(u64, Candidate)> {
+        let mut page = unordered_map_page(&self.candidates, cursor, limit);
+        page.items = self.internal_localize_all(EntityKind::Candidate, page.items, &lang);
+        page
+    }
+}
+
+impl VoteSmart {
+    /// Page over a set of district ids, with the districts loaded.
+    pub(crate) fn internal_districts_page(
+        &self,
+        ids: Option<UnorderedSet<u64>>,
+        cursor: Option<u64>,
+        limit: Option<u64>,
+        lang: &Option<String>,
+    ) -> Page<(u64, District)> {
+        let ids = match ids {
+            Some(ids) => ids,
+            None => return Page::from_vec(vec![], cursor, limit),
+        };
+        let page = vector_page(ids.as_vector(), cursor, limit);
+        let districts = page
+            .items
+            .into_iter()
+            .map(|id| (id, self.districts.get(&id).unwrap()))
+            .collect();
+        Page {
+            items: self.internal_localize_all(EntityKind::District, districts, lang),
+            total: page.total,
+            next_cursor: page.next_cursor,
+        }
+    }
+}
+
+pub(crate) fn vector_page<T>(v: &Vector<T>, cursor: Option<u64>, limit: Option<u64>) -> Page<T>
+where
+    T: BorshSerialize + BorshDeserialize,
+{
+    let total = v.len();
+    let (start, end) = page_bounds(total, cursor, limit);
+    Page {
+        items: (start..end).map(|index| v.get(index).unwrap()).collect(),
+        total,
+        next_cursor: next_cursor(total, end),
+    }
+}
+
+pub(crate) fn unordered_map_page<K, V>(
+    m: &UnorderedMap<K, V>,
+    cursor: Option<u64>,
+    limit: Option<u64>,
+) -> Page<(K, V)>
+where
+    K: BorshSerialize + BorshDeserialize,
+    V: BorshSerialize + BorshDeserialize,
+{
+    let keys = m.keys_as_vector();
+    let values = m.values_as_vector();
+    let total = keys.len();
+    let (start, end) = page_bounds(total, cursor, limit);
+    Page {
+        items: (start..end)
+            .map(|index| (keys.get(index).unwrap(), values.get(index).unwrap()))
+            .collect(),
+        total,
+        next_cursor: next_cursor(total, end),
+    }
+}
+
+fn page_bounds(total: u64, cursor: Option<u64>, limit: Option<u64>) -> (u64, u64) {
+    let start = std::cmp::min(cursor.unwrap_or(0), total);
+    let limit = limit.unwrap_or(DEFAULT_PAGE_LIMIT);
+    (start, std::cmp::min(total, start.saturating_add(limit)))
+}
+
+fn next_cursor(total: u64, end: u64) -> Option<u64> {
+    if end < total {
+        Some(end)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::*;
+
+    #[test]
+    fn districts_by_region_keep_legacy_range() {
+        let mut state = setup(3);
+        state.add_districts(vec![(4, district(4, Some(1)))]);
+        let ids = |districts: Vec<(u64, District)>| -> Vec<u64> {
+            districts.into_iter().map(|(id, _)| id).collect()
+        };
+        assert_eq!(
+            ids(state.get_districts_by_region(16, None, None, None)),
+            vec![1, 2, 3, 4]
+        );
+        assert_eq!(
+            ids(state.get_districts_by_region(16, Some(1), Some(3), None)),
+            vec![2, 3]
+        );
+        assert_eq!(
+            ids(state
+                .get_districts_by_region_page(16, None, None, None)
+                .items),
+            vec![1, 2, 3]
+        );
+    }
+}
@@ -0,0 +1,118 @@
+use crate::editing::assert_existing_rows;
+use crate::*;
+
+/// Optional details shown on the candidate page, kept apart from `Candidate`
+/// so that candidate lists stay small.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Default)]
+#[serde(crate = "near_sdk::serde")]
+pub struct CandidateProfile {
+    #[serde(default)]
+    pub bio: Option<String>,
+    /// IPFS CID or content hash of the photo.
+    #[serde(default)]
+    pub photo_cid: Option<String>,
+    #[serde(default)]
+    pub birth_year: Option<u16>,
+    #[serde(default)]
+    pub incumbent: Option<bool>,
+    /// Registration number issued by the election commission.
+    #[serde(default)]
+    pub registration_number: Option<String>,
+    /// Name as printed on the ballot, if it differs from the title.
+    #[serde(default)]
+    pub ballot_name: Option<String>,
+    #[serde(default)]
+    pub ballot_position: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct CandidateDetails {
+    #[serde(flatten)]
+    pub candidate: Candidate,
+    #[serde(flatten)]
+    pub profile: CandidateProfile,
+}
+
+#[near_bindgen]
+impl VoteSmart {
+    /// Replaces the whole profile of each candidate.
+    pub fn add_candidate_profiles(&mut self, profiles: Vec<(u64, CandidateProfile)>) {
+        self.assert_role(Role::DataEditor);
+        let ids: Vec<u64> = profiles.iter().map(|(id, _)| *id).collect();
+        assert_existing_rows(&self.candidates, "candidate", &ids);
+        for id in ids {
+            self.assert_not_frozen(
+                "Candidate",
+                id,
+                &self.internal_candidate_recommendations(id),
+            );
+        }
+        let mut changes = vec![];
+        for data in profiles {
+            let old = self.candidate_profiles.insert(&data.0, &data.1);
+            self.internal_charge_storage(StorageCollection::CandidateProfiles);
+            changes.push(EntityChange::new(
+                "candidate_profile",
+                data.0,
+                old.as_ref(),
+                Some(&data.1),
+            ));
+        }
+        emit_changes(changes);
+    }
+
+    pub fn get_candidate(&self, id: u64, lang: Option<String>) -> Option<CandidateDetails> {
+        let candidate = self.candidates.get(&id)?;
+        Some(CandidateDetails {
+            candidate: self.internal_localize(EntityKind::Candidate, id, candidate, &lang),
+            profile: self.candidate_profiles.get(&id).unwrap_or_default(),
+        })
+    }
+}
+
+impl VoteSmart {
+    /// Stores the candidate and moves it to its party's candidates. Returns the previous value.
+    pub(crate) fn internal_insert_candidate(
+        &mut self,
+        id: u64,
+        candidate: &Candidate,
+    ) -> Option<Candidate> {
+        let previous = self.candidates.insert(&id, candidate);
+        let moved = previous
+            .as_ref()
+            .is_none_or(|previous| previous.party_id != candidate.party_id);
+        if !moved {
+            return previous;
+        }
+        if let Some(ref previous) = previous {
+            self.internal_unlink_candidate(id, previous);
+        }
+        let party_id = candidate.party_id;
+        let mut candidates = self
+            .party_candidates
+            .get(&party_id)
+            .unwrap_or_else(|| UnorderedSet::new(StorageKey::PartyCandidatesByParty { party_id }));
+        candidates.insert(&id);
+        self.party_candidates.insert(&party_id, &candidates);
+        previous
+    }
+
+    pub(crate) fn internal_unlink_candidate(&mut self, id: u64, candidate: &Candidate) {
+        if let Some(mut ids) = self.party_candidates.get(&candidate.party_id) {
+            ids.remove(&id);
+            if ids.is_empty() {
+                self.party_candidates.remove(&candidate.party_id);
+            } else {
+                self.party_candidates.insert(&candidate.party_id, &ids);
+            }
+        }
+    }
+
+    pub(crate) fn internal_party_candidates(&self, party_id: u64) -> Vec<u64> {
+        self.party_candidates
+            .get(&party_id)
+            .map(|candidates| candidates.to_vec())
+            .unwrap_or_default()
+    }
+}
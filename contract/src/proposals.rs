@@ -0,0 +1,459 @@
+use crate::*;
+
+/// Default lifetime of a recommendation proposal, one week in nanoseconds.
+pub(crate) const DEFAULT_PROPOSAL_TTL: u64 = 7 * 24 * 60 * 60 * 1_000_000_000;
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ApprovalPolicy {
+    /// Distinct curator approvals needed to apply a batch, the proposer included.
+    /// Only accounts that still have the curator role when the batch is applied count.
+    pub approvals_required: u32,
+    /// Nanoseconds after which a pending batch can no longer be approved.
+    pub proposal_ttl: U64,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct RecommendationInput {
+    pub campaign_id: u64,
+    pub district_id: u64,
+    /// Ranked candidates, the ones after the first `seats` are fallbacks.
+    pub candidate_ids: Vec<u64>,
+    #[serde(default = "default_seats")]
+    pub seats: u32,
+    /// Explanation of the choice or a link to it.
+    #[serde(default)]
+    pub rationale: Option<String>,
+}
+
+fn default_seats() -> u32 {
+    1
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RecommendationChange {
+    Set(RecommendationInput),
+    Remove { campaign_id: u64, district_id: u64 },
+}
+
+impl RecommendationChange {
+    pub fn index(&self) -> RecommendationIndex {
+        match *self {
+            RecommendationChange::Set(ref input) => RecommendationIndex {
+                campaign_id: input.campaign_id,
+                district_id: input.district_id,
+            },
+            RecommendationChange::Remove {
+                campaign_id,
+                district_id,
+            } => RecommendationIndex {
+                campaign_id,
+                district_id,
+            },
+        }
+    }
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct RecommendationProposal {
+    pub proposer: AccountId,
+    pub changes: Vec<RecommendationChange>,
+    pub approvals: Vec<AccountId>,
+    pub created_at: U64,
+    pub expires_at: U64,
+}
+
+#[near_bindgen]
+impl VoteSmart {
+    // recommendations: [campaign_id: u64, district_id: u64, candidate_id: u64]
+    /// Stages the batch as a proposal approved by the caller and returns its id.
+    /// The batch is applied at once if the policy needs a single approval.
+    /// With `publish_at` the batch stays hidden from view methods until that time,
+    /// and its events carry only the ids until `emit_published_batch` is called.
+    pub fn add_recommendations(
+        &mut self,
+        recommendations: Vec<(u64, u64, u64)>,
+        publish_at: Option<U64>,
+    ) -> u64 {
+        self.internal_propose_recommendations(
+            recommendations
+                .into_iter()
+                .map(|(campaign_id, district_id, candidate_id)| {
+                    RecommendationChange::Set(RecommendationInput {
+                        campaign_id,
+                        district_id,
+                        candidate_ids: vec![candidate_id],
+                        seats: 1,
+                        rationale: None,
+                    })
+                })
+                .collect(),
+            publish_at,
+        )
+    }
+
+    /// Same as `add_recommendations`, with several seats and ranked fallback candidates per district.
+    pub fn add_detailed_recommendations(
+        &mut self,
+        recommendations: Vec<RecommendationInput>,
+        publish_at: Option<U64>,
+    ) -> u64 {
+        self.internal_propose_recommendations(
+            recommendations
+                .into_iter()
+                .map(RecommendationChange::Set)
+                .collect(),
+            publish_at,
+        )
+    }
+
+    // recommendations: [campaign_id: u64, district_id: u64]
+    /// Stages removal of the recommendations, same approval flow as `add_recommendations`.
+    pub fn remove_recommendations(
+        &mut self,
+        recommendations: Vec<(u64, u64)>,
+        publish_at: Option<U64>,
+    ) -> u64 {
+        self.internal_propose_recommendations(
+            recommendations
+                .into_iter()
+                .map(|(campaign_id, district_id)| RecommendationChange::Remove {
+                    campaign_id,
+                    district_id,
+                })
+                .collect(),
+            publish_at,
+        )
+    }
+
+    /// Adds the caller's approval. Returns true if the batch was applied.
+    pub fn approve_recommendations(&mut self, proposal_id: u64) -> bool {
+        self.assert_role(Role::RecommendationCurator);
+        let old = self
+            .recommendation_proposals
+            .get(&proposal_id)
+            .expect("Proposal not found");
+        assert!(
+            env::block_timestamp() < old.expires_at.0,
+            "Proposal expired"
+        );
+        let mut proposal = old.clone();
+        let account_id = env::predecessor_account_id();
+        assert!(
+            !proposal.approvals.contains(&account_id),
+            "Already approved"
+        );
+        proposal.approvals.push(account_id);
+        // referenced entities may have changed while the batch was pending
+        self.assert_valid_recommendation_changes(&proposal.changes, 0);
+        self.internal_approve_or_store(proposal_id, Some(old), proposal)
+    }
+
+    /// The proposer or an owner can cancel a pending batch, any curator can drop an expired one.
+    /// The publication time of the batch is removed with it.
+    pub fn cancel_recommendations(&mut self, proposal_id: u64) {
+        let proposal = self
+            .recommendation_proposals
+            .get(&proposal_id)
+            .expect("Proposal not found");
+        let account_id = env::predecessor_account_id();
+        let expired = env::block_timestamp() >= proposal.expires_at.0;
+        assert!(
+            account_id == proposal.proposer
+                || self.has_role(&account_id, Role::Owner)
+                || (expired && self.has_role(&account_id, Role::RecommendationCurator)),
+            "No access"
+        );
+        self.recommendation_proposals.remove(&proposal_id);
+        let change = EntityChange::proposal(proposal_id, Some(&proposal), None);
+        let mut changes = vec![if self.internal_batch_embargo(proposal_id).is_some() {
+            change.withheld_changes()
+        } else {
+            change
+        }];
+        // the batch never applies, so its publication time goes with it
+        if let Some(publish_at) = self.batch_publish_at.remove(&proposal_id) {
+            changes.push(EntityChange::new(
+                "batch_publish_at",
+                proposal_id,
+                Some(&U64(publish_at)),
+                None,
+            ));
+        }
+        self.internal_charge_storage(StorageCollection::Proposals);
+        emit_changes(changes);
+    }
+
+    pub fn get_recommendation_proposal(&self, proposal_id: u64) -> Option<RecommendationProposal> {
+        self.recommendation_proposals.get(&proposal_id)
+    }
+
+    pub fn get_recommendation_proposals(
+        &self,
+        cursor: Option<u64>,
+        limit: Option<u64>,
+    ) -> Page<(u64, RecommendationProposal)> {
+        unordered_map_page(&self.recommendation_proposals, cursor, limit)
+    }
+
+    pub fn set_approval_policy(&mut self, policy: ApprovalPolicy) {
+        self.assert_access();
+        assert!(
+            policy.approvals_required > 0,
+            "At least one approval is required"
+        );
+        let old = std::mem::replace(&mut self.approval_policy, policy);
+        emit_change(EntityChange::new(
+            "approval_policy",
+            (),
+            Some(&old),
+            Some(&self.approval_policy),
+        ));
+    }
+
+    pub fn get_approval_policy(&self) -> ApprovalPolicy {
+        self.approval_policy.clone()
+    }
+
+    fn internal_propose_recommendations(
+        &mut self,
+        changes: Vec<RecommendationChange>,
+        publish_at: Option<U64>,
+    ) -> u64 {
+        self.assert_role(Role::RecommendationCurator);
+        self.assert_valid_recommendation_changes(&changes, 0);
+        let mut events = vec![];
+        let proposal_id = self.internal_new_batch(publish_at, &mut events);
+        emit_changes(events);
+
+        let now = env::block_timestamp();
+        let proposal = RecommendationProposal {
+            proposer: env::predecessor_account_id(),
+            changes,
+            approvals: vec![env::predecessor_account_id()],
+            created_at: now.into(),
+            expires_at: (now + self.approval_policy.proposal_ttl.0).into(),
+        };
+        self.internal_approve_or_store(proposal_id, None, proposal);
+        proposal_id
+    }
+
+    /// Takes the next proposal id and stores the publication time of the batch.
+    pub(crate) fn internal_new_batch(
+        &mut self,
+        publish_at: Option<U64>,
+        changes: &mut Vec<EntityChange>,
+    ) -> u64 {
+        let proposal_id = self.next_proposal_id;
+        self.next_proposal_id += 1;
+        if let Some(publish_at) = publish_at {
+            self.batch_publish_at.insert(&proposal_id, &publish_at.0);
+            self.internal_charge_storage(StorageCollection::Proposals);
+            changes.push(EntityChange::new(
+                "batch_publish_at",
+                proposal_id,
+                None,
+                Some(&publish_at),
+            ));
+        }
+        proposal_id
+    }
+
+    /// `old` is the stored proposal, `None` for a new one.
+    fn internal_approve_or_store(
+        &mut self,
+        proposal_id: u64,
+        old: Option<RecommendationProposal>,
+        proposal: RecommendationProposal,
+    ) -> bool {
+        let mut changes = vec![];
+        // approvals of accounts whose role was revoked since do not count
+        let approvals = proposal
+            .approvals
+            .iter()
+            .filter(|account_id| self.has_role(account_id, Role::RecommendationCurator))
+            .count();
+        let applied = if approvals as u32 >= self.approval_policy.approvals_required {
+            if old.is_some() {
+                self.recommendation_proposals.remove(&proposal_id);
+                self.internal_charge_storage(StorageCollection::Proposals);
+                changes.push(EntityChange::proposal(proposal_id, old.as_ref(), None));
+            }
+            let origin = ChangeOrigin {
+                editor: proposal.proposer,
+                proposal_id: Some(proposal_id),
+                approvals: proposal.approvals,
+            };
+            self.internal_apply_recommendations(&proposal.changes, &origin, &mut changes);
+            true
+        } else {
+            self.recommendation_proposals
+                .insert(&proposal_id, &proposal);
+            self.internal_charge_storage(StorageCollection::Proposals);
+            let change = EntityChange::proposal(proposal_id, old.as_ref(), Some(&proposal));
+            changes.push(if self.internal_batch_embargo(proposal_id).is_some() {
+                change.withheld_changes()
+            } else {
+                change
+            });
+            false
+        };
+        emit_changes(changes);
+        applied
+    }
+
+    fn internal_apply_recommendations(
+        &mut self,
+        changes: &[RecommendationChange],
+        origin: &ChangeOrigin,
+        events: &mut Vec<EntityChange>,
+    ) {
+        let embargo = origin
+            .proposal_id
+            .and_then(|proposal_id| self.internal_batch_embargo(proposal_id));
+        let mut withheld = vec![];
+        for change in changes {
+            if let Some(event) = self.internal_apply_recommendation(change, origin, embargo) {
+                events.push(event);
+            }
+            if let RecommendationChange::Set(_) = change {
+                withheld.push(change.index());
+            }
+        }
+        if let (Some(proposal_id), Some(_)) = (origin.proposal_id, embargo) {
+            self.internal_withhold_batch(proposal_id, withheld);
+        }
+    }
+
+    /// Applies one change of the batch. With `embargo`, the publication time of the batch,
+    /// the returned event carries only the ids.
+    pub(crate) fn internal_apply_recommendation(
+        &mut self,
+        change: &RecommendationChange,
+        origin: &ChangeOrigin,
+        embargo: Option<u64>,
+    ) -> Option<EntityChange> {
+        let index = change.index();
+        let event = match *change {
+            RecommendationChange::Set(ref input) => {
+                let record = RecommendationRecord {
+                    candidate_ids: input.candidate_ids.clone(),
+                    seats: input.seats,
+                    rationale: input.rationale.clone(),
+                    updated_at: env::block_timestamp().into(),
+                    proposal_id: origin.proposal_id,
+                };
+                let old = self.internal_set_recommendation(&index, &record, origin);
+                EntityChange::recommendation(&index, old.as_ref(), Some(&record), origin)
+            }
+            RecommendationChange::Remove { .. } => {
+                let old = self.internal_remove_recommendation(&index, origin)?;
+                EntityChange::recommendation(&index, Some(&old), None, origin)
+            }
+        };
+        Some(match embargo {
+            Some(publish_at) => event.withheld(publish_at),
+            None => event,
+        })
+    }
+
+    /// Keeps the recommendations the embargoed batch set for `emit_published_batch`.
+    pub(crate) fn internal_withhold_batch(
+        &mut self,
+        proposal_id: u64,
+        indexes: Vec<RecommendationIndex>,
+    ) {
+        if !indexes.is_empty() {
+            self.withheld_batches.insert(&proposal_id, &indexes);
+            self.internal_charge_storage(StorageCollection::Proposals);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::*;
+    use near_sdk::test_utils::accounts;
+
+    /// Curators accounts(1) to accounts(3), batches need two approvals within 100 ns.
+    fn setup_with_curators() -> VoteSmart {
+        let mut state = setup(1);
+        for index in 1..=3 {
+            state.grant_role(accounts(index), Role::RecommendationCurator);
+        }
+        state.set_approval_policy(ApprovalPolicy {
+            approvals_required: 2,
+            proposal_ttl: 100.into(),
+        });
+        set_context(1, 0);
+        state.add_recommendations(vec![(1, 1, 10)], None);
+        state
+    }
+
+    fn recommended(state: &VoteSmart) -> Option<u64> {
+        state
+            .get_votesmart(1, 1, None)
+            .map(|recommendation| recommendation.candidate_id)
+    }
+
+    #[test]
+    fn batch_is_applied_by_last_approval() {
+        let mut state = setup_with_curators();
+        assert!(state.get_recommendation_proposal(0).is_some());
+        assert_eq!(recommended(&state), None);
+        set_context(2, 99);
+        assert!(state.approve_recommendations(0));
+        assert!(state.get_recommendation_proposal(0).is_none());
+        assert_eq!(recommended(&state), Some(10));
+    }
+
+    #[test]
+    #[should_panic(expected = "Already approved")]
+    fn proposer_cannot_approve_again() {
+        let mut state = setup_with_curators();
+        set_context(1, 0);
+        state.approve_recommendations(0);
+    }
+
+    #[test]
+    fn approval_of_revoked_curator_does_not_count() {
+        let mut state = setup_with_curators();
+        set_context(0, 0);
+        state.revoke_role(accounts(1), Role::RecommendationCurator);
+        set_context(2, 0);
+        assert!(!state.approve_recommendations(0));
+        assert_eq!(recommended(&state), None);
+        set_context(3, 0);
+        assert!(state.approve_recommendations(0));
+        assert_eq!(recommended(&state), Some(10));
+    }
+
+    #[test]
+    #[should_panic(expected = "Proposal expired")]
+    fn expired_batch_is_not_approved() {
+        let mut state = setup_with_curators();
+        set_context(2, 100);
+        state.approve_recommendations(0);
+    }
+
+    #[test]
+    #[should_panic(expected = "No access")]
+    fn other_curator_cannot_cancel_pending_batch() {
+        let mut state = setup_with_curators();
+        set_context(2, 99);
+        state.cancel_recommendations(0);
+    }
+
+    #[test]
+    fn expired_batch_is_cancelled_by_any_curator() {
+        let mut state = setup_with_curators();
+        set_context(2, 100);
+        state.cancel_recommendations(0);
+        assert!(state.get_recommendation_proposal(0).is_none());
+    }
+}
@@ -0,0 +1,315 @@
+use crate::*;
+
+/// Most districts resolved by one bulk view call, keeps it within the view gas limit.
+pub(crate) const MAX_BULK_DISTRICTS: u64 = 100;
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct RecommendationRecord {
+    /// Ranked candidates. The first `seats` active ones are recommended,
+    /// the rest are fallbacks for withdrawn or disqualified candidates.
+    pub candidate_ids: Vec<u64>,
+    /// Number of mandates elected in the district.
+    pub seats: u32,
+    /// Explanation of the choice or a link to it.
+    pub rationale: Option<String>,
+    pub updated_at: U64,
+    /// Batch that wrote the recommendation, used for its publication time.
+    pub proposal_id: Option<u64>,
+}
+
+/// Account and approvals behind a recommendation change.
+pub struct ChangeOrigin {
+    pub editor: AccountId,
+    /// Set when the change comes from an approved proposal.
+    pub proposal_id: Option<u64>,
+    pub approvals: Vec<AccountId>,
+}
+
+impl ChangeOrigin {
+    /// Change made directly by the caller, e.g. a cascading removal.
+    pub(crate) fn predecessor() -> Self {
+        Self {
+            editor: env::predecessor_account_id(),
+            proposal_id: None,
+            approvals: vec![],
+        }
+    }
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct RecommendationHistoryEntry {
+    /// None if the recommendation was removed.
+    pub recommendation: Option<RecommendationRecord>,
+    pub block_height: U64,
+    pub timestamp: U64,
+    pub editor: AccountId,
+    pub proposal_id: Option<u64>,
+    pub approvals: Vec<AccountId>,
+}
+
+#[near_bindgen]
+impl VoteSmart {
+    /// Recommendations that name the candidate, as the recommended one or as a fallback.
+    pub fn get_candidate_recommendations(&self, candidate_id: u64) -> Vec<RecommendationIndex> {
+        self.internal_candidate_recommendations(candidate_id)
+            .into_iter()
+            .filter(|index| self.internal_published_recommendation(index).is_some())
+            .collect()
+    }
+
+    /// `get_votesmart` for up to `MAX_BULK_DISTRICTS` districts, in the order given.
+    pub fn get_votesmart_bulk(
+        &self,
+        campaign_id: u64,
+        district_ids: Vec<u64>,
+        lang: Option<String>,
+    ) -> Vec<(u64, Option<Recommendation>)> {
+        assert!(
+            district_ids.len() as u64 <= MAX_BULK_DISTRICTS,
+            "Too many districts"
+        );
+        district_ids
+            .into_iter()
+            .map(|district_id| {
+                (
+                    district_id,
+                    self.get_votesmart(campaign_id, district_id, lang.clone()),
+                )
+            })
+            .collect()
+    }
+
+    /// `get_votesmart` for a page of the region's top level districts,
+    /// at most `MAX_BULK_DISTRICTS` per page.
+    pub fn get_region_recommendations(
+        &self,
+        campaign_id: u64,
+        region_id: u64,
+        cursor: Option<u64>,
+        limit: Option<u64>,
+        lang: Option<String>,
+    ) -> Page<(u64, Option<Recommendation>)> {
+        let limit = std::cmp::min(limit.unwrap_or(MAX_BULK_DISTRICTS), MAX_BULK_DISTRICTS);
+        let page = self.get_districts_by_region_page(region_id, cursor, Some(limit), None);
+        Page {
+            items: page
+                .items
+                .into_iter()
+                .map(|(district_id, _)| {
+                    (
+                        district_id,
+                        self.get_votesmart(campaign_id, district_id, lang.clone()),
+                    )
+                })
+                .collect(),
+            total: page.total,
+            next_cursor: page.next_cursor,
+        }
+    }
+
+    /// Every published change of the recommendation, oldest first.
+    pub fn get_recommendation_history(
+        &self,
+        campaign_id: u64,
+        district_id: u64,
+        cursor: Option<u64>,
+        limit: Option<u64>,
+    ) -> Page<RecommendationHistoryEntry> {
+        let entries = self
+            .recommendation_history
+            .get(&RecommendationIndex {
+                campaign_id,
+                district_id,
+            })
+            .map(|history| {
+                history
+                    .iter()
+                    .filter(|entry| self.internal_is_published(campaign_id, entry.proposal_id))
+                    .collect()
+            })
+            .unwrap_or_default();
+        Page::from_vec(entries, cursor, limit)
+    }
+}
+
+/// Every recommendation write goes through these helpers so that the
+/// lookup indexes stay in sync with `recommendations`.
+impl VoteSmart {
+    pub(crate) fn internal_set_recommendation(
+        &mut self,
+        index: &RecommendationIndex,
+        record: &RecommendationRecord,
+        origin: &ChangeOrigin,
+    ) -> Option<RecommendationRecord> {
+        self.internal_add_history_entry(index, Some(record.clone()), origin);
+        self.internal_update_merkle_leaf(index, Some(record));
+        let previous = self.recommendations.insert(index, record);
+        if let Some(ref previous) = previous {
+            self.internal_unlink_candidates(previous, index);
+        }
+
+        for candidate_id in record.candidate_ids.iter() {
+            let mut indexes = self
+                .candidate_recommendations
+                .get(candidate_id)
+                .unwrap_or_default();
+            if !indexes.contains(index) {
+                indexes.push(index.clone());
+                self.candidate_recommendations
+                    .insert(candidate_id, &indexes);
+            }
+        }
+
+        let mut districts = self.internal_recommended_districts(index.campaign_id);
+        districts.insert(&index.district_id);
+        self.recommended_districts
+            .insert(&index.campaign_id, &districts);
+        self.internal_charge_storage(StorageCollection::Recommendations);
+        previous
+    }
+
+    pub(crate) fn internal_remove_recommendation(
+        &mut self,
+        index: &RecommendationIndex,
+        origin: &ChangeOrigin,
+    ) -> Option<RecommendationRecord> {
+        let record = self.recommendations.remove(index)?;
+        self.internal_add_history_entry(index, None, origin);
+        self.internal_update_merkle_leaf(index, None);
+        self.internal_unlink_candidates(&record, index);
+
+        let mut districts = self.internal_recommended_districts(index.campaign_id);
+        districts.remove(&index.district_id);
+        if districts.is_empty() {
+            self.recommended_districts.remove(&index.campaign_id);
+        } else {
+            self.recommended_districts
+                .insert(&index.campaign_id, &districts);
+        }
+        self.internal_charge_storage(StorageCollection::Recommendations);
+        Some(record)
+    }
+
+    /// Up to `seats` first active candidates of the record and whether a fallback is among them.
+    pub(crate) fn internal_resolve_candidates(
+        &self,
+        record: &RecommendationRecord,
+    ) -> (Vec<(u64, Candidate)>, bool) {
+        let mut fallback_applied = false;
+        let candidates = record
+            .candidate_ids
+            .iter()
+            .enumerate()
+            .filter_map(|(position, candidate_id)| {
+                self.candidates
+                    .get(candidate_id)
+                    .filter(|candidate| candidate.status == CandidateStatus::Active)
+                    .map(|candidate| (position, *candidate_id, candidate))
+            })
+            .take(record.seats as usize)
+            .map(|(position, candidate_id, candidate)| {
+                fallback_applied |= position >= record.seats as usize;
+                (candidate_id, candidate)
+            })
+            .collect();
+        (candidates, fallback_applied)
+    }
+
+    /// Recommendations that point to the candidate.
+    pub(crate) fn internal_candidate_recommendations(
+        &self,
+        candidate_id: u64,
+    ) -> Vec<RecommendationIndex> {
+        self.candidate_recommendations
+            .get(&candidate_id)
+            .unwrap_or_default()
+    }
+
+    /// Recommendations given for the district in any campaign.
+    pub(crate) fn internal_district_recommendations(
+        &self,
+        district_id: u64,
+    ) -> Vec<RecommendationIndex> {
+        self.campaigns
+            .keys_as_vector()
+            .iter()
+            .map(|campaign_id| RecommendationIndex {
+                campaign_id,
+                district_id,
+            })
+            .filter(|index| self.recommendations.contains_key(index))
+            .collect()
+    }
+
+    /// Recommendations given in the campaign.
+    pub(crate) fn internal_campaign_recommendations(
+        &self,
+        campaign_id: u64,
+    ) -> Vec<RecommendationIndex> {
+        self.recommended_districts
+            .get(&campaign_id)
+            .map(|districts| {
+                districts
+                    .iter()
+                    .map(|district_id| RecommendationIndex {
+                        campaign_id,
+                        district_id,
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    fn internal_add_history_entry(
+        &mut self,
+        index: &RecommendationIndex,
+        recommendation: Option<RecommendationRecord>,
+        origin: &ChangeOrigin,
+    ) {
+        let mut history = self.recommendation_history.get(index).unwrap_or_else(|| {
+            Vector::new(StorageKey::RecommendationHistoryByIndex {
+                campaign_id: index.campaign_id,
+                district_id: index.district_id,
+            })
+        });
+        history.push(&RecommendationHistoryEntry {
+            recommendation,
+            block_height: env::block_index().into(),
+            timestamp: env::block_timestamp().into(),
+            editor: origin.editor.clone(),
+            proposal_id: origin.proposal_id,
+            approvals: origin.approvals.clone(),
+        });
+        self.recommendation_history.insert(index, &history);
+    }
+
+    fn internal_recommended_districts(&self, campaign_id: u64) -> UnorderedSet<u64> {
+        self.recommended_districts
+            .get(&campaign_id)
+            .unwrap_or_else(|| {
+                UnorderedSet::new(StorageKey::RecommendedDistrictsByCampaign { campaign_id })
+            })
+    }
+
+    fn internal_unlink_candidates(
+        &mut self,
+        record: &RecommendationRecord,
+        index: &RecommendationIndex,
+    ) {
+        for candidate_id in record.candidate_ids.iter() {
+            let mut indexes = self
+                .candidate_recommendations
+                .get(candidate_id)
+                .unwrap_or_default();
+            indexes.retain(|existing| existing != index);
+            if indexes.is_empty() {
+                self.candidate_recommendations.remove(candidate_id);
+            } else {
+                self.candidate_recommendations
+                    .insert(candidate_id, &indexes);
+            }
+        }
+    }
+}
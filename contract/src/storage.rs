@@ -0,0 +1,142 @@
+use crate::*;
+use near_sdk::json_types::U128;
+use near_sdk::Balance;
+
+#[derive(
+    BorshDeserialize,
+    BorshSerialize,
+    Serialize,
+    Deserialize,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Hash,
+)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(rename_all = "snake_case")]
+pub enum StorageCollection {
+    Campaigns,
+    Parties,
+    Regions,
+    /// Districts with the region and parent unit indexes.
+    Districts,
+    Candidates,
+    CandidateProfiles,
+    Translations,
+    /// Recommendations with their history, indexes and Merkle trees.
+    Recommendations,
+    /// Pending proposals and batch publication times.
+    Proposals,
+    Roles,
+}
+
+const STORAGE_COLLECTIONS: [StorageCollection; 10] = [
+    StorageCollection::Campaigns,
+    StorageCollection::Parties,
+    StorageCollection::Regions,
+    StorageCollection::Districts,
+    StorageCollection::Candidates,
+    StorageCollection::CandidateProfiles,
+    StorageCollection::Translations,
+    StorageCollection::Recommendations,
+    StorageCollection::Proposals,
+    StorageCollection::Roles,
+];
+
+/// Storage usage at the last accounted write. Not stored, it starts
+/// at the usage the call began with.
+pub struct StorageMeter {
+    checkpoint: u64,
+}
+
+impl Default for StorageMeter {
+    fn default() -> Self {
+        Self {
+            checkpoint: env::storage_usage(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct StorageStats {
+    /// Bytes used by the rows of each collection.
+    pub collections: Vec<(StorageCollection, U64)>,
+    /// Bytes of the contract state and of the parties and regions kept from the first release.
+    pub other: U64,
+    pub total: U64,
+    /// Bytes the account balance can still cover.
+    pub available: U64,
+    /// Balance not needed for the current storage.
+    pub available_balance: U128,
+}
+
+#[near_bindgen]
+impl VoteSmart {
+    pub fn get_storage_stats(&self) -> StorageStats {
+        let collections: Vec<(StorageCollection, U64)> = STORAGE_COLLECTIONS
+            .iter()
+            .map(|collection| {
+                let bytes = self
+                    .collection_storage
+                    .get(collection)
+                    .copied()
+                    .unwrap_or(0);
+                (*collection, bytes.into())
+            })
+            .collect();
+        let total = env::storage_usage();
+        let accounted: u64 = collections.iter().map(|(_, bytes)| bytes.0).sum();
+        let available_balance = storage_balance().saturating_sub(storage_cost(total));
+        StorageStats {
+            collections,
+            other: total.saturating_sub(accounted).into(),
+            total: total.into(),
+            available: ((available_balance / env::storage_byte_cost()) as u64).into(),
+            available_balance: available_balance.into(),
+        }
+    }
+}
+
+impl VoteSmart {
+    /// Adds the storage written since the last call to the collection. Panics as soon
+    /// as the balance can't cover the storage, before the rest of the batch is written.
+    pub(crate) fn internal_charge_storage(&mut self, collection: StorageCollection) {
+        let usage = env::storage_usage();
+        let checkpoint = std::mem::replace(&mut self.storage_meter.checkpoint, usage);
+        let bytes = self.collection_storage.entry(collection).or_insert(0);
+        if usage >= checkpoint {
+            *bytes += usage - checkpoint;
+        } else {
+            *bytes = bytes.saturating_sub(checkpoint - usage);
+        }
+
+        let required = storage_cost(usage);
+        let balance = storage_balance();
+        if required > balance {
+            env::panic(
+                format!(
+                    "Not enough balance for storage: {} bytes need {} yoctoNEAR, the account has {}",
+                    usage, required, balance
+                )
+                .as_bytes(),
+            );
+        }
+    }
+
+    /// Leaves the storage written since the last call unaccounted.
+    pub(crate) fn internal_skip_storage(&mut self) {
+        self.storage_meter.checkpoint = env::storage_usage();
+    }
+}
+
+fn storage_cost(bytes: u64) -> Balance {
+    Balance::from(bytes) * env::storage_byte_cost()
+}
+
+/// Locked balance covers storage as well.
+fn storage_balance() -> Balance {
+    env::account_balance() + env::account_locked_balance()
+}
@@ -0,0 +1,66 @@
+//! Contract and context helpers shared by the unit tests.
+use crate::*;
+use near_sdk::test_utils::{accounts, VMContextBuilder};
+use near_sdk::{testing_env, MockedBlockchain};
+
+/// Starts a call by `accounts(caller)` at `timestamp`, the logs and the gas start over.
+pub(crate) fn set_context(caller: usize, timestamp: u64) {
+    testing_env!(VMContextBuilder::new()
+        .current_account_id(accounts(5))
+        .predecessor_account_id(accounts(caller))
+        .block_timestamp(timestamp)
+        .build());
+}
+
+/// Contract of `accounts(0)` with published campaign 1, party 1, region 16,
+/// top level districts 1 to `districts` and active candidates 10 and 11 of party 1.
+pub(crate) fn setup(districts: u64) -> VoteSmart {
+    set_context(0, 0);
+    let mut state = VoteSmart::new(None);
+    state.add_campaign(1, "Duma".to_string(), 0.into(), CampaignLevel::Federal);
+    state.set_campaign_status(1, CampaignStatus::Published);
+    state.add_parties(vec![(1, "Party".to_string())]);
+    state.add_regions(vec![(
+        16,
+        Region {
+            title: "Tatarstan".to_string(),
+        },
+    )]);
+    for ids in (1..=districts).collect::<Vec<_>>().chunks(50) {
+        set_context(0, 0);
+        state.add_districts(ids.iter().map(|id| (*id, district(*id, None))).collect());
+    }
+    state.add_candidates([10, 11].iter().map(|id| (*id, candidate(*id, 1))).collect());
+    state
+}
+
+pub(crate) fn district(id: u64, parent_id: Option<u64>) -> District {
+    District {
+        region_id: 16,
+        title: format!("District {}", id),
+        unit_type: if parent_id.is_some() {
+            UnitType::PollingStation
+        } else {
+            UnitType::District
+        },
+        parent_id,
+        address: None,
+    }
+}
+
+pub(crate) fn candidate(id: u64, party_id: u64) -> Candidate {
+    Candidate {
+        title: format!("Candidate {}", id),
+        party_id,
+        status: CandidateStatus::Active,
+    }
+}
+
+/// Parsed `EVENT_JSON:` logs of the current call.
+pub(crate) fn events() -> Vec<near_sdk::serde_json::Value> {
+    near_sdk::test_utils::get_logs()
+        .iter()
+        .filter_map(|log| log.strip_prefix("EVENT_JSON:"))
+        .map(|log| near_sdk::serde_json::from_str(log).unwrap())
+        .collect()
+}
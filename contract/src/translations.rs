@@ -0,0 +1,232 @@
+use crate::validation::assert_no_invalid_rows;
+use crate::*;
+
+/// Language of the `title` fields when none is set with `set_default_language`.
+pub(crate) const DEFAULT_LANGUAGE: &str = "ru";
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(rename_all = "snake_case")]
+pub enum EntityKind {
+    Campaign,
+    Party,
+    Region,
+    District,
+    Candidate,
+}
+
+impl EntityKind {
+    fn name(self) -> &'static str {
+        match self {
+            EntityKind::Campaign => "campaign",
+            EntityKind::Party => "party",
+            EntityKind::Region => "region",
+            EntityKind::District => "district",
+            EntityKind::Candidate => "candidate",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct TranslationInput {
+    pub entity: EntityKind,
+    pub id: u64,
+    /// Language code, e.g. "tt" or "ru-Latn".
+    pub lang: String,
+    pub title: String,
+}
+
+/// Entities whose title can be translated.
+pub trait Titled {
+    fn title_mut(&mut self) -> &mut String;
+}
+
+impl Titled for String {
+    fn title_mut(&mut self) -> &mut String {
+        self
+    }
+}
+
+impl Titled for Campaign {
+    fn title_mut(&mut self) -> &mut String {
+        &mut self.title
+    }
+}
+
+impl Titled for Region {
+    fn title_mut(&mut self) -> &mut String {
+        &mut self.title
+    }
+}
+
+impl Titled for District {
+    fn title_mut(&mut self) -> &mut String {
+        &mut self.title
+    }
+}
+
+impl Titled for Candidate {
+    fn title_mut(&mut self) -> &mut String {
+        &mut self.title
+    }
+}
+
+#[near_bindgen]
+impl VoteSmart {
+    /// Titles in other languages than the default one. An empty title removes the translation.
+    pub fn add_translations(&mut self, translations: Vec<TranslationInput>) {
+        self.assert_role(Role::DataEditor);
+        let errors = translations
+            .iter()
+            .enumerate()
+            .filter_map(|(row, input)| {
+                if !self.internal_entity_exists(input.entity, input.id) {
+                    Some(format!(
+                        "row {}: unknown {} {}",
+                        row,
+                        input.entity.name(),
+                        input.id
+                    ))
+                } else if input.lang == self.default_language {
+                    Some(format!(
+                        "row {}: {} is the default language, use update methods",
+                        row, input.lang
+                    ))
+                } else {
+                    None
+                }
+            })
+            .collect();
+        assert_no_invalid_rows(errors);
+
+        let mut changes = vec![];
+        for input in translations {
+            self.assert_entity_not_frozen(input.entity, input.id);
+            let key = (input.entity, input.id);
+            let old = self.translations.get(&key);
+            let mut titles = old.clone().unwrap_or_default();
+            if input.title.is_empty() {
+                titles.remove(&input.lang);
+            } else {
+                titles.insert(input.lang, input.title);
+            }
+            let new = if titles.is_empty() {
+                self.translations.remove(&key);
+                None
+            } else {
+                self.translations.insert(&key, &titles);
+                Some(titles)
+            };
+            match (&old, &new) {
+                (None, Some(_)) => self.translated_entities += 1,
+                (Some(_), None) => self.translated_entities -= 1,
+                _ => {}
+            }
+            self.internal_charge_storage(StorageCollection::Translations);
+            if old.is_some() || new.is_some() {
+                changes.push(EntityChange::new(
+                    "translation",
+                    key,
+                    old.as_ref(),
+                    new.as_ref(),
+                ));
+            }
+        }
+        emit_changes(changes);
+    }
+
+    pub fn get_translations(&self, entity: EntityKind, id: u64) -> HashMap<String, String> {
+        self.translations.get(&(entity, id)).unwrap_or_default()
+    }
+
+    /// Changes the language the stored `title` fields are read as. Refused while translations
+    /// are stored, they would no longer match the titles they were made for.
+    pub fn set_default_language(&mut self, lang: String) {
+        self.assert_role(Role::Owner);
+        assert_eq!(
+            self.translated_entities, 0,
+            "Remove the translations before changing the default language"
+        );
+        let old = std::mem::replace(&mut self.default_language, lang);
+        emit_change(EntityChange::new(
+            "default_language",
+            (),
+            Some(&old),
+            Some(&self.default_language),
+        ));
+    }
+
+    pub fn get_default_language(&self) -> String {
+        self.default_language.clone()
+    }
+}
+
+impl VoteSmart {
+    /// Title in `lang`, or `None` if the default title should be used.
+    pub(crate) fn internal_translation(
+        &self,
+        entity: EntityKind,
+        id: u64,
+        lang: &Option<String>,
+    ) -> Option<String> {
+        let lang = lang
+            .as_ref()
+            .filter(|lang| **lang != self.default_language)?;
+        self.translations
+            .get(&(entity, id))
+            .and_then(|mut titles| titles.remove(lang))
+    }
+
+    pub(crate) fn internal_localize<T: Titled>(
+        &self,
+        entity: EntityKind,
+        id: u64,
+        mut value: T,
+        lang: &Option<String>,
+    ) -> T {
+        if let Some(title) = self.internal_translation(entity, id, lang) {
+            *value.title_mut() = title;
+        }
+        value
+    }
+
+    pub(crate) fn internal_localize_all<T: Titled>(
+        &self,
+        entity: EntityKind,
+        items: Vec<(u64, T)>,
+        lang: &Option<String>,
+    ) -> Vec<(u64, T)> {
+        if lang.is_none() {
+            return items;
+        }
+        items
+            .into_iter()
+            .map(|(id, value)| (id, self.internal_localize(entity, id, value, lang)))
+            .collect()
+    }
+
+    pub(crate) fn internal_remove_translations(
+        &mut self,
+        entity: EntityKind,
+        id: u64,
+        changes: &mut Vec<EntityChange>,
+    ) {
+        let key = (entity, id);
+        if let Some(titles) = self.translations.remove(&key) {
+            self.translated_entities -= 1;
+            self.internal_charge_storage(StorageCollection::Translations);
+            changes.push(EntityChange::new("translation", key, Some(&titles), None));
+        }
+    }
+
+    fn internal_entity_exists(&self, entity: EntityKind, id: u64) -> bool {
+        match entity {
+            EntityKind::Campaign => self.campaigns.get(&id).is_some(),
+            EntityKind::Party => self.parties.get(&id).is_some(),
+            EntityKind::Region => self.regions.get(&id).is_some(),
+            EntityKind::District => self.districts.get(&id).is_some(),
+            EntityKind::Candidate => self.candidates.get(&id).is_some(),
+        }
+    }
+}
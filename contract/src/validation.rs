@@ -0,0 +1,187 @@
+use crate::*;
+use std::collections::HashMap;
+
+/// Number of offending rows listed in the panic message.
+const MAX_REPORTED_ROWS: usize = 20;
+
+impl VoteSmart {
+    /// Parents are looked up in the batch first, then in the stored districts.
+    /// Rows are numbered from `first_row` in the panic message.
+    pub(crate) fn assert_valid_districts(&self, districts: &[(u64, District)], first_row: usize) {
+        let batch: HashMap<u64, &District> = districts
+            .iter()
+            .map(|(id, district)| (*id, district))
+            .collect();
+        let region_of = |id: u64| {
+            batch
+                .get(&id)
+                .map(|district| district.region_id)
+                .or_else(|| self.districts.get(&id).map(|district| district.region_id))
+        };
+        let parent_of = |id: u64| match batch.get(&id) {
+            Some(district) => district.parent_id,
+            None => self
+                .districts
+                .get(&id)
+                .and_then(|district| district.parent_id),
+        };
+
+        let mut errors = vec![];
+        for (row, (id, district)) in districts.iter().enumerate() {
+            let row = first_row + row;
+            if self.regions.get(&district.region_id).is_none() {
+                errors.push(format!(
+                    "row {} (district {}): unknown region_id {}",
+                    row, id, district.region_id
+                ));
+            }
+            if district.unit_type == UnitType::Region {
+                errors.push(format!(
+                    "row {} (district {}): regions are added with add_regions",
+                    row, id
+                ));
+            }
+            if let Some(parent_id) = district.parent_id {
+                match region_of(parent_id) {
+                    None => errors.push(format!(
+                        "row {} (district {}): unknown parent_id {}",
+                        row, id, parent_id
+                    )),
+                    Some(region_id) if region_id != district.region_id => errors.push(format!(
+                        "row {} (district {}): parent_id {} is in another region",
+                        row, id, parent_id
+                    )),
+                    _ => {
+                        // a chain longer than the number of units has a cycle
+                        let mut ancestor_id = Some(parent_id);
+                        let mut steps = 0;
+                        while let Some(current) = ancestor_id {
+                            if current == *id || steps > batch.len() as u64 + self.districts.len() {
+                                errors.push(format!(
+                                    "row {} (district {}): parent_id {} makes a cycle",
+                                    row, id, parent_id
+                                ));
+                                break;
+                            }
+                            ancestor_id = parent_of(current);
+                            steps += 1;
+                        }
+                    }
+                }
+            }
+            for child_id in self.internal_district_children(*id) {
+                if region_of(child_id) != Some(district.region_id) {
+                    errors.push(format!(
+                        "row {} (district {}): child unit {} is in another region",
+                        row, id, child_id
+                    ));
+                }
+            }
+        }
+        assert_no_invalid_rows(errors);
+    }
+
+    pub(crate) fn assert_valid_candidates(
+        &self,
+        candidates: &[(u64, Candidate)],
+        first_row: usize,
+    ) {
+        let errors = candidates
+            .iter()
+            .enumerate()
+            .filter(|(_, (_, candidate))| self.parties.get(&candidate.party_id).is_none())
+            .map(|(row, (id, candidate))| {
+                format!(
+                    "row {} (candidate {}): unknown party_id {}",
+                    first_row + row,
+                    id,
+                    candidate.party_id
+                )
+            })
+            .collect();
+        assert_no_invalid_rows(errors);
+    }
+
+    pub(crate) fn assert_valid_recommendation_changes(
+        &self,
+        changes: &[RecommendationChange],
+        first_row: usize,
+    ) {
+        let mut errors = vec![];
+        for (row, change) in changes.iter().enumerate() {
+            let row = first_row + row;
+            let campaign_id = change.index().campaign_id;
+            if let Some(campaign) = self.campaigns.get(&campaign_id) {
+                if campaign.frozen_at.is_some() {
+                    errors.push(format!(
+                        "row {}: campaign_id {} is frozen",
+                        row, campaign_id
+                    ));
+                } else if !campaign.status.is_open() {
+                    errors.push(format!(
+                        "row {}: campaign_id {} is closed",
+                        row, campaign_id
+                    ));
+                }
+            }
+            match *change {
+                RecommendationChange::Set(ref input) => {
+                    let mut unknown = vec![];
+                    if self.campaigns.get(&input.campaign_id).is_none() {
+                        unknown.push(format!("campaign_id {}", input.campaign_id));
+                    }
+                    if self.districts.get(&input.district_id).is_none() {
+                        unknown.push(format!("district_id {}", input.district_id));
+                    }
+                    for candidate_id in input.candidate_ids.iter() {
+                        if self.candidates.get(candidate_id).is_none() {
+                            unknown.push(format!("candidate_id {}", candidate_id));
+                        }
+                    }
+                    if !unknown.is_empty() {
+                        errors.push(format!("row {}: unknown {}", row, unknown.join(", ")));
+                    }
+                    if input.seats == 0 || input.candidate_ids.len() < input.seats as usize {
+                        errors.push(format!(
+                            "row {}: {} seats need as many candidates",
+                            row, input.seats
+                        ));
+                    }
+                    let mut candidate_ids = input.candidate_ids.clone();
+                    candidate_ids.sort_unstable();
+                    candidate_ids.dedup();
+                    if candidate_ids.len() != input.candidate_ids.len() {
+                        errors.push(format!("row {}: candidate listed more than once", row));
+                    }
+                }
+                RecommendationChange::Remove {
+                    campaign_id,
+                    district_id,
+                } => {
+                    if !self.recommendations.contains_key(&change.index()) {
+                        errors.push(format!(
+                            "row {}: no recommendation for campaign_id {}, district_id {}",
+                            row, campaign_id, district_id
+                        ));
+                    }
+                }
+            }
+        }
+        assert_no_invalid_rows(errors);
+    }
+}
+
+/// Rejects the whole batch if any row failed validation.
+pub(crate) fn assert_no_invalid_rows(errors: Vec<String>) {
+    if errors.is_empty() {
+        return;
+    }
+    let mut message = format!(
+        "Invalid rows: {}",
+        errors[..errors.len().min(MAX_REPORTED_ROWS)].join("; ")
+    );
+    if errors.len() > MAX_REPORTED_ROWS {
+        message.push_str(&format!("; and {} more", errors.len() - MAX_REPORTED_ROWS));
+    }
+    env::panic(message.as_bytes());
+}